@@ -0,0 +1,168 @@
+//! The `regex!` macro, which compiles a `regex-engine` pattern into a DFA while the crate using it
+//! compiles.
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// Compile a pattern given as a string literal into a minimized DFA, and expand to a
+/// `&'static regex_engine::StaticDFA` matching the same strings as `RegExp::new` would. Lexing,
+/// parsing and building the DFA all happen at compile time, and an invalid pattern is a compile
+/// error. The error points at the whole literal; its message gives the byte range of the pattern
+/// at fault and the text there.
+///
+/// ```
+/// use regex_engine_macros::regex;
+///
+/// let identifier = regex!(r"(a|b|\_)(a|b|\_|0|1)*");
+/// assert!(identifier.matches("_a01"));
+/// assert!(!identifier.matches("0a"));
+///
+/// static KEYWORD: &regex_engine::StaticDFA = regex!("if|else");
+/// assert!(KEYWORD.matches("else"));
+/// ```
+///
+/// ```compile_fail
+/// let unclosed = regex_engine_macros::regex!("(ab");
+/// ```
+#[proc_macro]
+pub fn regex(input: TokenStream) -> TokenStream {
+    let (pattern, span) = match pattern(input) {
+        Ok(pattern) => pattern,
+        Err((message, span)) => return compile_error(&message, span),
+    };
+    match regex_engine::table::source(&pattern) {
+        Ok(source) => format!(
+            "{{ static DFA: ::regex_engine::table::StaticDFA = {}; &DFA }}",
+            source
+        )
+        .parse()
+        .unwrap(),
+        Err(error) => {
+            let snippet = pattern.get(error.span.start..error.span.end).unwrap_or("");
+            let message = format!(
+                "invalid pattern at {}..{} `{}`: {}",
+                error.span.start, error.span.end, snippet, error
+            );
+            compile_error(&message, span)
+        }
+    }
+}
+
+/// the value of the single string literal in `input`, and where it is
+fn pattern(input: TokenStream) -> Result<(String, Span), (String, Span)> {
+    let mut tokens = input.into_iter();
+    let (first, rest) = (tokens.next(), tokens.next());
+    match (first, rest) {
+        // the argument of a `macro_rules!` macro arrives wrapped in an invisible group
+        (Some(TokenTree::Group(group)), None) if group.delimiter() == Delimiter::None => {
+            pattern(group.stream())
+        }
+        (Some(TokenTree::Literal(literal)), None) => {
+            let span = literal.span();
+            match unquote(&literal.to_string()) {
+                Some(pattern) => Ok((pattern, span)),
+                None => Err(("expected a string literal".to_string(), span)),
+            }
+        }
+        (Some(token), _) => Err(("expected a string literal".to_string(), token.span())),
+        (None, _) => Err(("expected a string literal".to_string(), Span::call_site())),
+    }
+}
+
+/// the value of a string literal as written in source, or `None` for any other literal
+fn unquote(source: &str) -> Option<String> {
+    if let Some(raw) = source.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let body = raw.get(hashes + 1..raw.len().checked_sub(hashes + 1)?)?;
+        return Some(body.to_string());
+    }
+    let body = source.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            value.push(ch);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            '0' => value.push('\0'),
+            '\\' => value.push('\\'),
+            '\'' => value.push('\''),
+            '"' => value.push('"'),
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+                value.push(char::from(u8::from_str_radix(&digits, 16).ok()?));
+            }
+            'u' => {
+                let digits: String = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|&c| c != '}')
+                    .filter(|&c| c != '_')
+                    .collect();
+                value.push(char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?);
+            }
+            // a line continuation skips the line break and the indentation after it
+            escape @ ('\n' | '\r') => {
+                if escape == '\r' && chars.next() != Some('\n') {
+                    return None;
+                }
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            }
+            _ => return None,
+        }
+    }
+    Some(value)
+}
+
+/// `compile_error!("message")`, reported at `span`
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let mut group = Group::new(Delimiter::Parenthesis, TokenTree::from(literal).into());
+    group.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    [
+        TokenTree::from(Ident::new("compile_error", span)),
+        TokenTree::from(bang),
+        TokenTree::from(group),
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod macros_tests {
+    use super::*;
+
+    #[test]
+    fn unquote_strings() {
+        assert_eq!(unquote(r#""a|b""#).as_deref(), Some("a|b"));
+        assert_eq!(
+            unquote(r#""\n\r\t\0\\\'\"""#).as_deref(),
+            Some("\n\r\t\0\\'\"")
+        );
+        assert_eq!(unquote(r#""\x41\x7a""#).as_deref(), Some("Az"));
+        assert_eq!(unquote(r#""\u{e9}\u{1_F600}""#).as_deref(), Some("é😀"));
+        assert_eq!(unquote("\"a\\\n    b\"").as_deref(), Some("ab"));
+        assert_eq!(unquote("\"a\\\r\n\tb\"").as_deref(), Some("ab"));
+        assert_eq!(unquote(r#""\q""#), None);
+    }
+
+    #[test]
+    fn unquote_raw_strings() {
+        assert_eq!(unquote(r#"r"\_""#).as_deref(), Some(r"\_"));
+        assert_eq!(unquote(r##"r#"a"b"#"##).as_deref(), Some(r#"a"b"#));
+        assert_eq!(unquote(r###"r##"#"##"###).as_deref(), Some("#"));
+    }
+
+    #[test]
+    fn unquote_rejects_other_literals() {
+        for literal in [r#"b"a""#, r#"br"a""#, r#"c"a""#, r#"cr"a""#, "'a'", "1"] {
+            assert_eq!(unquote(literal), None, "{}", literal);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::meta::MatchKind;
+
+/// Searches for many literals at once (Aho–Corasick): a trie of the literals whose failure links
+/// lead from each node to the longest proper suffix of its string that is also in the trie, so
+/// that every occurrence of every literal is seen in one pass over the haystack.
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    literals: Vec<String>,
+    /// trie edges
+    goto: Vec<BTreeMap<char, usize>>,
+    fail: Vec<usize>,
+    /// literals ending at each node, directly or through failure links
+    outputs: Vec<Vec<usize>>,
+    /// literal spelled by each node, if it is one
+    terminal: Vec<Option<usize>>,
+    max_len: usize,
+}
+
+const ROOT: usize = 0;
+
+impl AhoCorasick {
+    pub fn new(literals: Vec<String>) -> Self {
+        let mut goto = vec![BTreeMap::new()];
+        let mut terminal = vec![None];
+        for (id, literal) in literals.iter().enumerate() {
+            let mut node = ROOT;
+            for ch in literal.chars() {
+                node = match goto[node].get(&ch) {
+                    Some(&next) => next,
+                    None => {
+                        let next = goto.len();
+                        goto.push(BTreeMap::new());
+                        terminal.push(None);
+                        goto[node].insert(ch, next);
+                        next
+                    }
+                };
+            }
+            terminal[node].get_or_insert(id);
+        }
+
+        // breadth-first, so that failure targets are done before the nodes that use them
+        let mut fail = vec![ROOT; goto.len()];
+        let mut outputs: Vec<Vec<usize>> = terminal
+            .iter()
+            .map(|id| id.iter().copied().collect())
+            .collect();
+        let mut queue: VecDeque<usize> = goto[ROOT].values().copied().collect();
+        while let Some(node) = queue.pop_front() {
+            for (&ch, &child) in &goto[node] {
+                let mut target = fail[node];
+                while target != ROOT && !goto[target].contains_key(&ch) {
+                    target = fail[target];
+                }
+                fail[child] = goto[target].get(&ch).copied().unwrap_or(ROOT);
+                let inherited = outputs[fail[child]].clone();
+                outputs[child].extend(inherited);
+                queue.push_back(child);
+            }
+        }
+
+        let max_len = literals
+            .iter()
+            .map(|literal| literal.len())
+            .max()
+            .unwrap_or(0);
+        Self {
+            literals,
+            goto,
+            fail,
+            outputs,
+            terminal,
+            max_len,
+        }
+    }
+
+    /// Whether `input` is one of the literals.
+    pub fn is_full_match(&self, input: &str) -> bool {
+        let mut node = ROOT;
+        for ch in input.chars() {
+            match self.goto[node].get(&ch) {
+                Some(&next) => node = next,
+                None => return false,
+            }
+        }
+        self.terminal[node].is_some()
+    }
+
+    /// The leftmost occurrence of a literal as a byte range. Among those starting at the same
+    /// offset, `kind` prefers the literal listed first or the longest one.
+    pub fn find(&self, haystack: &str, kind: MatchKind) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize, usize)> = None;
+        let mut node = ROOT;
+        for (pos, ch) in haystack.char_indices() {
+            // nothing ending from here on can start at or before the best match
+            if best.is_some_and(|(start, _, _)| pos >= start + self.max_len) {
+                break;
+            }
+            while node != ROOT && !self.goto[node].contains_key(&ch) {
+                node = self.fail[node];
+            }
+            node = self.goto[node].get(&ch).copied().unwrap_or(ROOT);
+
+            let end = pos + ch.len_utf8();
+            for &id in &self.outputs[node] {
+                let start = end - self.literals[id].len();
+                let better = match best {
+                    None => true,
+                    Some((best_start, best_end, best_id)) => {
+                        start < best_start
+                            || start == best_start
+                                && match kind {
+                                    MatchKind::LeftmostFirst => id < best_id,
+                                    MatchKind::LeftmostLongest => end > best_end,
+                                }
+                    }
+                };
+                if better {
+                    best = Some((start, end, id));
+                }
+            }
+        }
+        best.map(|(start, end, _)| (start, end))
+    }
+}
+
+#[cfg(test)]
+mod aho_corasick_tests {
+    use super::*;
+
+    fn searcher(literals: &[&str]) -> AhoCorasick {
+        AhoCorasick::new(literals.iter().map(|literal| literal.to_string()).collect())
+    }
+
+    #[test]
+    fn leftmost() {
+        let ac = searcher(&["error", "warn", "fatal", "panic"]);
+        assert_eq!(
+            ac.find("a panic, then an error", MatchKind::LeftmostFirst),
+            Some((2, 7))
+        );
+        assert_eq!(ac.find("all fine", MatchKind::LeftmostFirst), None);
+
+        // failure links: `abcd` fails to `bcd` after reading `abc`
+        let ac = searcher(&["abcx", "bcd"]);
+        assert_eq!(ac.find("abcd", MatchKind::LeftmostFirst), Some((1, 4)));
+
+        let ac = searcher(&["a", "ab", "b"]);
+        assert_eq!(ac.find("xab", MatchKind::LeftmostFirst), Some((1, 2)));
+        assert_eq!(ac.find("xab", MatchKind::LeftmostLongest), Some((1, 3)));
+        // `b` ends first, but `ab` starts further left
+        let ac = searcher(&["b", "ab"]);
+        assert_eq!(ac.find("ab", MatchKind::LeftmostFirst), Some((0, 2)));
+    }
+
+    #[test]
+    fn full_match() {
+        let ac = searcher(&["warn", "warning"]);
+        assert!(ac.is_full_match("warn"));
+        assert!(ac.is_full_match("warning"));
+        assert!(!ac.is_full_match("warni"));
+        assert!(!ac.is_full_match(""));
+    }
+}
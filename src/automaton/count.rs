@@ -0,0 +1,318 @@
+use std::collections::BTreeSet;
+
+use crate::util::biguint::BigUint;
+
+use super::{dfa::DFA, State, StateSet};
+
+/// How the number of accepted strings of length `n` grows with `n`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Growth {
+    /// The language has `size` strings, the longest of which has length `max_len`
+    /// (`None` for the empty language).
+    Finite {
+        size: BigUint,
+        max_len: Option<usize>,
+    },
+    /// Θ(n^degree) strings of length `n`, e.g. degree 0 for `a*` and degree 1 for `a*b*`.
+    Polynomial { degree: usize },
+    /// Θ(rate^n) strings of length `n`; `rate` is the spectral radius of the transition matrix.
+    Exponential { rate: f64 },
+}
+
+/// `counts[k][s]` is the number of strings of length `k` leading from state `s` to acceptance
+pub fn path_counts(dfa: &DFA, max_len: usize) -> Vec<Vec<BigUint>> {
+    let mut counts = vec![accept_counts(dfa)];
+    for k in 1..=max_len {
+        counts.push(next_counts(dfa, &counts[k - 1]));
+    }
+    counts
+}
+
+/// row 0 of [`path_counts`]: 1 for accept states, 0 elsewhere
+fn accept_counts(dfa: &DFA) -> Vec<BigUint> {
+    dfa.states()
+        .map(|state| {
+            if dfa.is_accept(state) {
+                BigUint::one()
+            } else {
+                BigUint::zero()
+            }
+        })
+        .collect()
+}
+
+/// row `k + 1` of [`path_counts`] from row `k`
+fn next_counts(dfa: &DFA, counts: &[BigUint]) -> Vec<BigUint> {
+    dfa.states()
+        .map(|state| {
+            dfa.map[state.id]
+                .values()
+                .fold(BigUint::zero(), |acc, next| acc.add(&counts[next.id]))
+        })
+        .collect()
+}
+
+/// whether the language is finite, i.e. no cycle runs through a state that is both reachable
+/// from the start and able to reach an accept state
+pub fn is_finite(dfa: &DFA) -> bool {
+    let useful = useful_states(dfa);
+
+    // Kahn's algorithm over the useful subgraph: a leftover state means a cycle
+    let mut in_degree = vec![0usize; dfa.map.len()];
+    for state in &useful {
+        for next in dfa.map[state.id].values() {
+            if useful.contains(next) {
+                in_degree[next.id] += 1;
+            }
+        }
+    }
+    let mut que: Vec<State> = useful
+        .iter()
+        .filter(|state| in_degree[state.id] == 0)
+        .copied()
+        .collect();
+    let mut visited = 0;
+    while let Some(state) = que.pop() {
+        visited += 1;
+        for next in dfa.map[state.id].values() {
+            if useful.contains(next) {
+                in_degree[next.id] -= 1;
+                if in_degree[next.id] == 0 {
+                    que.push(*next);
+                }
+            }
+        }
+    }
+    visited == useful.len()
+}
+
+pub fn reachable_states(dfa: &DFA) -> StateSet {
+    let mut done = BTreeSet::new();
+    let mut que = vec![dfa.start];
+    while let Some(state) = que.pop() {
+        if done.insert(state) {
+            que.extend(dfa.map[state.id].values().copied());
+        }
+    }
+    done
+}
+
+/// states that are reachable from the start and can reach an accept state
+pub fn useful_states(dfa: &DFA) -> StateSet {
+    reachable_states(dfa)
+        .intersection(&dfa.live_states())
+        .copied()
+        .collect()
+}
+
+/// the entry for the start state in row `len` of [`path_counts`], keeping one row at a time
+pub fn count_of_length(dfa: &DFA, len: usize) -> BigUint {
+    let mut counts = accept_counts(dfa);
+    for _ in 0..len {
+        counts = next_counts(dfa, &counts);
+    }
+    counts.swap_remove(dfa.start.id)
+}
+
+pub fn growth(dfa: &DFA) -> Growth {
+    let useful = useful_states(dfa);
+
+    if is_finite(dfa) {
+        // without cycles no useful path is longer than the number of states
+        let mut row = accept_counts(dfa);
+        let mut size = BigUint::zero();
+        let mut max_len = None;
+        for len in 0..=dfa.map.len() {
+            if len > 0 {
+                row = next_counts(dfa, &row);
+            }
+            if !row[dfa.start.id].is_zero() {
+                size = size.add(&row[dfa.start.id]);
+                max_len = Some(len);
+            }
+        }
+        return Growth::Finite { size, max_len };
+    }
+
+    let components = components(dfa, &useful);
+    let mut component_of = vec![usize::MAX; dfa.map.len()];
+    for (i, component) in components.iter().enumerate() {
+        for state in component {
+            component_of[state.id] = i;
+        }
+    }
+
+    // a strongly connected component with more edges than states contains two distinct cycles,
+    // so the number of paths through it grows exponentially
+    let mut cyclic = vec![false; components.len()];
+    for (i, component) in components.iter().enumerate() {
+        let edges = component
+            .iter()
+            .flat_map(|state| dfa.map[state.id].values())
+            .filter(|next| component_of[next.id] == i)
+            .count();
+        if edges > component.len() {
+            return Growth::Exponential {
+                rate: spectral_radius(dfa, &useful),
+            };
+        }
+        cyclic[i] = edges > 0;
+    }
+
+    // the degree is one less than the largest number of cycles a single path can pass through.
+    // Tarjan's algorithm emits components in reverse topological order, so successors come first.
+    let mut cycles = vec![0usize; components.len()];
+    for (i, component) in components.iter().enumerate() {
+        let successors = component
+            .iter()
+            .flat_map(|state| dfa.map[state.id].values())
+            .filter(|next| useful.contains(next) && component_of[next.id] != i)
+            .map(|next| cycles[component_of[next.id]])
+            .max()
+            .unwrap_or(0);
+        cycles[i] = successors + usize::from(cyclic[i]);
+    }
+    Growth::Polynomial {
+        degree: cycles[component_of[dfa.start.id]] - 1,
+    }
+}
+
+/// strongly connected components of the subgraph induced by `states`, in reverse topological
+/// order (Tarjan's algorithm)
+fn components(dfa: &DFA, states: &StateSet) -> Vec<Vec<State>> {
+    struct Tarjan<'a> {
+        dfa: &'a DFA,
+        states: &'a StateSet,
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<State>,
+        next_index: usize,
+        components: Vec<Vec<State>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, state: State) {
+            self.index[state.id] = Some(self.next_index);
+            self.low[state.id] = self.next_index;
+            self.next_index += 1;
+            self.stack.push(state);
+            self.on_stack[state.id] = true;
+
+            for &next in self.dfa.map[state.id].values() {
+                if !self.states.contains(&next) {
+                    continue;
+                }
+                match self.index[next.id] {
+                    None => {
+                        self.visit(next);
+                        self.low[state.id] = self.low[state.id].min(self.low[next.id]);
+                    }
+                    Some(index) if self.on_stack[next.id] => {
+                        self.low[state.id] = self.low[state.id].min(index);
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.low[state.id]) == self.index[state.id] {
+                let mut component = vec![];
+                loop {
+                    let member = self.stack.pop().unwrap();
+                    self.on_stack[member.id] = false;
+                    component.push(member);
+                    if member == state {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    let len = dfa.map.len();
+    let mut tarjan = Tarjan {
+        dfa,
+        states,
+        index: vec![None; len],
+        low: vec![0; len],
+        on_stack: vec![false; len],
+        stack: vec![],
+        next_index: 0,
+        components: vec![],
+    };
+    for &state in states {
+        if tarjan.index[state.id].is_none() {
+            tarjan.visit(state);
+        }
+    }
+    tarjan.components
+}
+
+/// largest eigenvalue of the transition matrix restricted to `states`, by power iteration.
+/// Averaging the growth over many steps keeps periodic components from oscillating.
+fn spectral_radius(dfa: &DFA, states: &StateSet) -> f64 {
+    const STEPS: usize = 1000;
+
+    let mut vector = vec![1.0f64; dfa.map.len()];
+    let mut log_growth = 0.0;
+    for _ in 0..STEPS {
+        let mut next = vec![0.0f64; dfa.map.len()];
+        for state in states {
+            next[state.id] = dfa.map[state.id]
+                .values()
+                .filter(|n| states.contains(n))
+                .map(|n| vector[n.id])
+                .sum();
+        }
+        let norm = next.iter().cloned().fold(0.0, f64::max);
+        if norm == 0.0 {
+            return 0.0;
+        }
+        log_growth += norm.ln();
+        vector = next.into_iter().map(|v| v / norm).collect();
+    }
+    (log_growth / STEPS as f64).exp()
+}
+
+#[cfg(test)]
+mod count_tests {
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    fn compile(regex: &str) -> DFA {
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        Parser::new(tokens).parse().nfa2dfa()
+    }
+
+    #[test]
+    fn counts_strings_of_each_length() {
+        let dfa = compile("(a|b)*c");
+        assert_eq!(count_of_length(&dfa, 0), BigUint::zero());
+        assert_eq!(count_of_length(&dfa, 1), BigUint::one());
+        assert_eq!(count_of_length(&dfa, 4), BigUint::from(8));
+        assert_eq!(
+            count_of_length(&dfa, 101).to_string(),
+            "1267650600228229401496703205376"
+        );
+    }
+
+    #[test]
+    fn classifies_growth() {
+        assert_eq!(
+            growth(&compile("a|(bc)|(b|c)(d|e)")),
+            Growth::Finite {
+                size: BigUint::from(6),
+                max_len: Some(2)
+            }
+        );
+        assert_eq!(growth(&compile("a*")), Growth::Polynomial { degree: 0 });
+        assert_eq!(growth(&compile("a*b*c*")), Growth::Polynomial { degree: 2 });
+        match growth(&compile("(a|bc)*")) {
+            // the golden ratio: count(n) = count(n - 1) + count(n - 2)
+            Growth::Exponential { rate } => assert!((rate - 1.618034).abs() < 1e-3),
+            other => panic!("unexpected growth: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::compiler::fragment::ByteClasses;
+
+use super::dfa::DFA;
+
+/// Byte DFA with a flat transition table indexed by state and byte class. A typical pattern
+/// distinguishes only a handful of classes, so each state takes a few entries instead of 256.
+#[derive(Debug, Clone)]
+pub struct DenseDFA {
+    classes: ByteClasses,
+    /// `table[state * stride + class]` is the next state; state 0 is the dead state
+    table: Vec<usize>,
+    stride: usize,
+    start: usize,
+    accepts: Vec<bool>,
+}
+
+impl DenseDFA {
+    const DEAD: usize = 0;
+
+    /// `dfa` must be a byte automaton whose labels are the characters U+0000..=U+00FF
+    pub fn new(dfa: &DFA, classes: ByteClasses) -> Self {
+        let stride = classes.len();
+        let representatives = classes.representatives();
+
+        // shift every state up by one to make room for the dead state
+        let mut table = vec![Self::DEAD; (dfa.map.len() + 1) * stride];
+        let mut accepts = vec![false; dfa.map.len() + 1];
+        for state in dfa.states() {
+            for (class, &byte) in representatives.iter().enumerate() {
+                if let Some(next) = dfa.next(state, char::from(byte)) {
+                    table[(state.id + 1) * stride + class] = next.id + 1;
+                }
+            }
+            accepts[state.id + 1] = dfa.is_accept(state);
+        }
+
+        Self {
+            classes,
+            table,
+            stride,
+            start: dfa.start.id + 1,
+            accepts,
+        }
+    }
+
+    pub fn is_match(&self, input: &[u8]) -> bool {
+        let mut state = self.start;
+        for &byte in input {
+            state = self.table[state * self.stride + self.classes.get(byte) as usize];
+            if state == Self::DEAD {
+                return false;
+            }
+        }
+        self.accepts[state]
+    }
+}
+
+#[cfg(test)]
+mod dense_tests {
+    use crate::automaton::utf8::utf8_fragment;
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    #[test]
+    fn table_is_indexed_by_class() {
+        let tokens = Lexer::new("(a|b|c)*d".to_string()).tokenize();
+        let fragment = utf8_fragment(&Parser::new(tokens).parse());
+        let classes = fragment.byte_classes();
+        // [00-60] a b c d [65-FF]
+        assert_eq!(classes.len(), 6);
+        assert_eq!(classes.get(b'\0'), classes.get(b'`'));
+        assert_eq!(classes.get(b'e'), classes.get(0xFF));
+
+        let dense = DenseDFA::new(&fragment.build().nfa2dfa().minimize(), classes);
+        // dead, the loop on a|b|c and after d
+        assert_eq!(dense.table.len(), 3 * 6);
+        assert!(dense.is_match(b"abcad"));
+        assert!(!dense.is_match(b"abce"));
+    }
+}
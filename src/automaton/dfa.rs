@@ -1,7 +1,146 @@
-use super::StateSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-pub struct DFA<'a> {
-    pub start: StateSet,
+use super::{State, StateSet};
+
+/// DFA materialized from an NFA by subset construction.
+///
+/// `subsets[i]` is the set of NFA states that DFA state `i` stands for, and `map[i]` holds its
+/// outgoing transitions. A missing transition leads to the (implicit) dead state.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone)]
+pub struct DFA {
+    pub start: State,
     pub accepts: StateSet,
-    pub transition: Box<dyn (Fn(&StateSet, String) -> StateSet) + 'a>,
+    pub subsets: Vec<StateSet>,
+    pub map: Vec<BTreeMap<char, State>>,
+}
+
+impl DFA {
+    pub fn next(&self, state: State, input: char) -> Option<State> {
+        self.map[state.id].get(&input).copied()
+    }
+
+    pub fn is_accept(&self, state: State) -> bool {
+        self.accepts.contains(&state)
+    }
+
+    pub fn states(&self) -> impl Iterator<Item = State> {
+        (0..self.map.len()).map(State::new)
+    }
+
+    /// every character that appears on some transition, in ascending order
+    pub fn alphabet(&self) -> Vec<char> {
+        let mut alphabet: Vec<char> = self.map.iter().flat_map(|m| m.keys().copied()).collect();
+        alphabet.sort();
+        alphabet.dedup();
+        alphabet
+    }
+
+    /// states from which some accept state can be reached
+    pub fn live_states(&self) -> StateSet {
+        let mut live = self.accepts.clone();
+        loop {
+            let before = live.len();
+            for state in self.states() {
+                if self.map[state.id].values().any(|next| live.contains(next)) {
+                    live.insert(state);
+                }
+            }
+            if live.len() == before {
+                return live;
+            }
+        }
+    }
+
+    /// Equivalent DFA with the fewest states, by Moore's partition refinement. States that are
+    /// unreachable or cannot reach an accept state are dropped along the way.
+    pub fn minimize(&self) -> DFA {
+        let mut reachable = vec![false; self.map.len()];
+        let mut que = vec![self.start];
+        while let Some(state) = que.pop() {
+            if !reachable[state.id] {
+                reachable[state.id] = true;
+                que.extend(self.map[state.id].values().copied());
+            }
+        }
+        let live = self.live_states();
+        let useful: Vec<State> = self
+            .states()
+            .filter(|state| reachable[state.id] && live.contains(state))
+            .collect();
+
+        if !live.contains(&self.start) {
+            return DFA {
+                start: State::new(0),
+                accepts: StateSet::new(),
+                subsets: vec![self.subsets[self.start.id].clone()],
+                map: vec![BTreeMap::new()],
+            };
+        }
+
+        // refine until the block of a state is determined by its own block and its successors'
+        let mut block = vec![usize::MAX; self.map.len()];
+        for &state in &useful {
+            block[state.id] = usize::from(self.is_accept(state));
+        }
+        let mut count = useful
+            .iter()
+            .map(|state| block[state.id])
+            .collect::<BTreeSet<_>>()
+            .len();
+        loop {
+            let mut signatures = HashMap::new();
+            let mut next_block = vec![usize::MAX; self.map.len()];
+            for &state in &useful {
+                let signature = (
+                    block[state.id],
+                    self.map[state.id]
+                        .iter()
+                        .filter(|(_, next)| block[next.id] != usize::MAX)
+                        .map(|(&input, next)| (input, block[next.id]))
+                        .collect::<Vec<_>>(),
+                );
+                let len = signatures.len();
+                next_block[state.id] = *signatures.entry(signature).or_insert(len);
+            }
+            // refinement only ever splits blocks, so an unchanged count means nothing moved
+            let stable = signatures.len() == count;
+            count = signatures.len();
+            block = next_block;
+            if stable {
+                break;
+            }
+        }
+
+        // renumber blocks so that the start state comes first
+        let mut renumber = HashMap::new();
+        renumber.insert(block[self.start.id], State::new(0));
+        for &state in &useful {
+            let len = renumber.len();
+            renumber.entry(block[state.id]).or_insert(State::new(len));
+        }
+
+        let mut subsets = vec![StateSet::new(); renumber.len()];
+        let mut map = vec![BTreeMap::new(); renumber.len()];
+        let mut accepts = StateSet::new();
+        for &state in &useful {
+            let new = renumber[&block[state.id]];
+            subsets[new.id].extend(self.subsets[state.id].iter().copied());
+            for (&input, next) in &self.map[state.id] {
+                if let Some(&next) = renumber.get(&block[next.id]) {
+                    map[new.id].insert(input, next);
+                }
+            }
+            if self.is_accept(state) {
+                accepts.insert(new);
+            }
+        }
+
+        DFA {
+            start: State::new(0),
+            accepts,
+            subsets,
+            map,
+        }
+    }
 }
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use crate::compiler::ast::{NewNode, NodeKind};
+
+use super::dfa::DFA;
+
+/// Generalized NFA whose edges are labelled with whole expressions, used for state elimination.
+/// Each state pair has at most one edge; parallel labels are merged into a union.
+#[allow(clippy::upper_case_acronyms)]
+struct GNFA {
+    start: usize,
+    accept: usize,
+    edges: BTreeMap<(usize, usize), NewNode>,
+}
+
+impl GNFA {
+    fn add_edge(&mut self, from: usize, to: usize, label: NewNode) {
+        let label = match self.edges.remove(&(from, to)) {
+            Some(existing) => union(existing, label),
+            None => label,
+        };
+        self.edges.insert((from, to), label);
+    }
+
+    /// Remove `state`, rerouting every path `p -> state -> r` through a direct `p -> r` edge
+    /// labelled `in loop* out`.
+    fn eliminate(&mut self, state: usize) {
+        let self_loop = self.edges.remove(&(state, state)).map(star);
+        let incoming: Vec<(usize, NewNode)> = self
+            .edges
+            .iter()
+            .filter(|((_, to), _)| *to == state)
+            .map(|((from, _), label)| (*from, label.clone()))
+            .collect();
+        let outgoing: Vec<(usize, NewNode)> = self
+            .edges
+            .iter()
+            .filter(|((from, _), _)| *from == state)
+            .map(|((_, to), label)| (*to, label.clone()))
+            .collect();
+        self.edges
+            .retain(|(from, to), _| *from != state && *to != state);
+
+        for (from, in_label) in &incoming {
+            for (to, out_label) in &outgoing {
+                let mut label = in_label.clone();
+                if let Some(self_loop) = &self_loop {
+                    label = concat(label, self_loop.clone());
+                }
+                self.add_edge(*from, *to, concat(label, out_label.clone()));
+            }
+        }
+    }
+
+    /// Eliminate every state but the start and accept, always picking the state that creates
+    /// the fewest new edges, and return the label left between start and accept.
+    fn reduce(mut self, states: usize) -> Option<NewNode> {
+        let mut remaining: Vec<usize> = (0..states).collect();
+        while !remaining.is_empty() {
+            let cost = |state: usize| {
+                let incoming = self.edges.keys().filter(|(_, to)| *to == state).count();
+                let outgoing = self.edges.keys().filter(|(from, _)| *from == state).count();
+                incoming * outgoing
+            };
+            let (index, _) = remaining
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, state)| cost(**state))
+                .unwrap();
+            let state = remaining.swap_remove(index);
+            self.eliminate(state);
+        }
+        self.edges.remove(&(self.start, self.accept))
+    }
+}
+
+/// Regular expression for the language of `dfa`, or `None` if the language is empty.
+pub fn dfa_to_ast(dfa: &DFA) -> Option<NewNode> {
+    let states = dfa.map.len();
+    let mut gnfa = GNFA {
+        start: states,
+        accept: states + 1,
+        edges: BTreeMap::new(),
+    };
+    gnfa.add_edge(gnfa.start, dfa.start.id, epsilon());
+    for state in dfa.states() {
+        for (input, next) in &dfa.map[state.id] {
+            gnfa.add_edge(state.id, next.id, character(input.to_string()));
+        }
+        if dfa.is_accept(state) {
+            gnfa.add_edge(state.id, gnfa.accept, epsilon());
+        }
+    }
+    gnfa.reduce(states)
+}
+
+fn epsilon() -> NewNode {
+    character("".to_string())
+}
+
+fn character(ch: String) -> NewNode {
+    NewNode::new(NodeKind::Char, Some(ch), None, None)
+}
+
+fn union(left: NewNode, right: NewNode) -> NewNode {
+    if left == right {
+        return left;
+    }
+    NewNode::new(NodeKind::Union, None, Some(left), Some(right))
+}
+
+fn concat(left: NewNode, right: NewNode) -> NewNode {
+    if left.is_empty() {
+        return right;
+    }
+    if right.is_empty() {
+        return left;
+    }
+    NewNode::new(NodeKind::Concat, None, Some(left), Some(right))
+}
+
+fn star(origin: NewNode) -> NewNode {
+    if origin.is_empty() || matches!(origin, NewNode::Star(_)) {
+        return origin;
+    }
+    NewNode::new(NodeKind::Star, None, Some(origin), None)
+}
+
+#[cfg(test)]
+mod eliminate_tests {
+    use crate::automaton::{generate::Examples, nfa::NFA, runtime::Runtime};
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    fn compile(regex: &str) -> NFA {
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        Parser::new(tokens).parse()
+    }
+
+    /// the expression printed from the automaton must describe the same language
+    fn assert_round_trip(regex: &str, expected: &str) {
+        let dfa = compile(regex).nfa2dfa().minimize();
+        let pattern = dfa_to_ast(&dfa).unwrap().to_string();
+        assert_eq!(pattern, expected);
+
+        let round_trip = compile(&pattern).nfa2dfa().minimize();
+        assert_eq!(round_trip.map.len(), dfa.map.len());
+        for example in Examples::new(&dfa).take(50) {
+            assert!(Runtime::new(&round_trip).run(example));
+        }
+    }
+
+    #[test]
+    fn dfa_to_pattern() {
+        assert_round_trip("abc", "abc");
+        assert_round_trip("(a|b)*", "(?:a|b)*");
+        assert_round_trip("a*a*", "a*");
+        assert_round_trip("ab|ac", "a(?:b|c)");
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::BTreeSet;
+
+use crate::util::{biguint::BigUint, rng::Rng};
+
+use super::{
+    count::{is_finite, path_counts},
+    dfa::DFA,
+    runtime::Runtime,
+    State, StateSet,
+};
+
+/// accepted strings in shortlex order: shorter strings first, then lexicographically
+pub struct Examples<'a> {
+    dfa: &'a DFA,
+    /// `exact[k]` holds the states that reach acceptance in exactly `k` steps
+    exact: Vec<StateSet>,
+    /// no accepted string is longer than this; `None` for infinite languages
+    max_len: Option<usize>,
+    len: Option<usize>,
+    /// DFS over the strings of the current length: a state and the index of its next edge
+    stack: Vec<(State, usize)>,
+    buf: Vec<char>,
+}
+
+impl<'a> Examples<'a> {
+    pub fn new(dfa: &'a DFA) -> Self {
+        // a finite language cannot accept anything longer than the number of states
+        let max_len = if is_finite(dfa) {
+            Some(dfa.map.len())
+        } else {
+            None
+        };
+        Self {
+            dfa,
+            exact: vec![dfa.accepts.clone()],
+            max_len,
+            len: None,
+            stack: vec![],
+            buf: vec![],
+        }
+    }
+
+    fn exact(&mut self, k: usize) -> &StateSet {
+        while self.exact.len() <= k {
+            let prev = self.exact.last().unwrap();
+            let row = self
+                .dfa
+                .states()
+                .filter(|state| self.dfa.map[state.id].values().any(|n| prev.contains(n)))
+                .collect();
+            self.exact.push(row);
+        }
+        &self.exact[k]
+    }
+}
+
+impl Iterator for Examples<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(&(state, edge)) = self.stack.last() else {
+                let len = self.len.map_or(0, |len| len + 1);
+                if self.max_len.is_some_and(|max_len| len > max_len) {
+                    return None;
+                }
+                self.len = Some(len);
+                let start = self.dfa.start;
+                if self.exact(len).contains(&start) {
+                    self.stack.push((start, 0));
+                }
+                continue;
+            };
+
+            let len = self.len.unwrap();
+            let depth = self.stack.len() - 1;
+            if depth == len {
+                let example = self.buf.iter().collect();
+                self.stack.pop();
+                self.buf.pop();
+                return Some(example);
+            }
+
+            match self.dfa.map[state.id].iter().nth(edge) {
+                Some((&input, &next)) => {
+                    self.stack.last_mut().unwrap().1 += 1;
+                    if self.exact(len - depth - 1).contains(&next) {
+                        self.stack.push((next, 0));
+                        self.buf.push(input);
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    self.buf.pop();
+                }
+            }
+        }
+    }
+}
+
+/// uniformly random accepted strings of a fixed length
+pub struct RandomExamples<'a> {
+    dfa: &'a DFA,
+    counts: Vec<Vec<BigUint>>,
+    rng: Rng,
+}
+
+impl<'a> RandomExamples<'a> {
+    pub fn new(dfa: &'a DFA, len: usize, seed: u64) -> Self {
+        Self {
+            dfa,
+            counts: path_counts(dfa, len),
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl Iterator for RandomExamples<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.counts.len() - 1;
+        let total = &self.counts[len][self.dfa.start.id];
+        if total.is_zero() {
+            return None;
+        }
+
+        // pick the index of the string in shortlex order, then walk down to it
+        let mut target = total.random_below(&mut self.rng);
+        let mut state = self.dfa.start;
+        let mut example = String::new();
+        for remaining in (0..len).rev() {
+            for (&input, &next) in &self.dfa.map[state.id] {
+                let count = &self.counts[remaining][next.id];
+                if target < *count {
+                    example.push(input);
+                    state = next;
+                    break;
+                }
+                target = target.sub(count);
+            }
+        }
+        Some(example)
+    }
+}
+
+/// rejected strings one edit (deletion, substitution or insertion) away from an accepted one
+pub fn near_misses(dfa: &DFA, limit: usize) -> Vec<String> {
+    let mut alphabet = dfa.alphabet();
+    // a character outside the alphabet is always rejected, which guarantees progress
+    let foreign = ('a'..='z')
+        .chain('0'..='9')
+        .find(|c| !alphabet.contains(c))
+        .unwrap_or('\u{FFFD}');
+    alphabet.push(foreign);
+
+    let mut done = BTreeSet::new();
+    let mut misses = vec![];
+    for example in Examples::new(dfa) {
+        if misses.len() >= limit {
+            break;
+        }
+        let chars: Vec<char> = example.chars().collect();
+        let mut candidates = vec![];
+        for i in 0..chars.len() {
+            let mut deleted = chars.clone();
+            deleted.remove(i);
+            candidates.push(deleted);
+        }
+        for i in 0..chars.len() {
+            for &c in &alphabet {
+                let mut substituted = chars.clone();
+                substituted[i] = c;
+                candidates.push(substituted);
+            }
+        }
+        for i in 0..=chars.len() {
+            for &c in &alphabet {
+                let mut inserted = chars.clone();
+                inserted.insert(i, c);
+                candidates.push(inserted);
+            }
+        }
+
+        for candidate in candidates {
+            let candidate: String = candidate.into_iter().collect();
+            if misses.len() < limit
+                && !done.contains(&candidate)
+                && !Runtime::new(dfa).run(candidate.clone())
+            {
+                done.insert(candidate.clone());
+                misses.push(candidate);
+            }
+        }
+    }
+    misses
+}
+
+#[cfg(test)]
+mod generate_tests {
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    fn compile(regex: &str) -> DFA {
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        Parser::new(tokens).parse().nfa2dfa()
+    }
+
+    #[test]
+    fn examples_in_shortlex_order() {
+        let dfa = compile("a|(bc)|(def)*");
+        let examples: Vec<String> = Examples::new(&dfa).take(5).collect();
+        assert_eq!(examples, vec!["", "a", "bc", "def", "defdef"]);
+
+        let dfa = compile("(a|b)(a|b)");
+        let examples: Vec<String> = Examples::new(&dfa).collect();
+        assert_eq!(examples, vec!["aa", "ab", "ba", "bb"]);
+    }
+
+    #[test]
+    fn random_examples_are_accepted() {
+        let dfa = compile("(a|bc)*");
+        let examples: Vec<String> = RandomExamples::new(&dfa, 7, 42).take(20).collect();
+        assert_eq!(examples.len(), 20);
+        for example in examples {
+            assert_eq!(example.len(), 7);
+            assert!(Runtime::new(&dfa).run(example));
+        }
+        assert_eq!(RandomExamples::new(&compile("ab"), 3, 0).next(), None);
+    }
+
+    #[test]
+    fn near_misses_are_rejected() {
+        let dfa = compile("ab*");
+        let misses = near_misses(&dfa, 10);
+        assert_eq!(misses.len(), 10);
+        assert_eq!(misses[0], "");
+        for miss in misses {
+            assert!(!Runtime::new(&dfa).run(miss));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::{nfa::NFA, StateSet};
+
+/// Subset construction on demand: only the DFA states that the input actually visits are built,
+/// and each of them once, so the cost stays linear in the input even when the full DFA would
+/// be too large to materialize.
+///
+/// An unanchored lazy DFA starts a new match at every position, so its state after some input
+/// is accepting whenever a match ends there. State 0 is the start state.
+pub struct LazyDFA<'a> {
+    nfa: &'a NFA,
+    accepts: &'a StateSet,
+    cache: LazyCache,
+}
+
+/// The states of a [`LazyDFA`] built so far, which can be kept to build on in later searches.
+pub struct LazyCache {
+    unanchored: bool,
+    subsets: Vec<StateSet>,
+    index: HashMap<StateSet, usize>,
+    map: Vec<HashMap<char, usize>>,
+}
+
+impl<'a> LazyDFA<'a> {
+    pub fn new(nfa: &'a NFA) -> Self {
+        Self::with_cache(nfa, LazyCache::new(nfa, false))
+    }
+
+    pub fn unanchored(nfa: &'a NFA) -> Self {
+        Self::with_cache(nfa, LazyCache::new(nfa, true))
+    }
+
+    /// Resume with the states of an earlier lazy DFA, which must have been built for `nfa`.
+    pub fn with_cache(nfa: &'a NFA, cache: LazyCache) -> Self {
+        let accepts = nfa
+            .accepts
+            .as_ref()
+            .unwrap_or_else(|| panic!("nfa.accepts is None"));
+        Self {
+            nfa,
+            accepts,
+            cache,
+        }
+    }
+
+    /// The states built so far, for [`LazyDFA::with_cache`].
+    pub fn into_cache(self) -> LazyCache {
+        self.cache
+    }
+
+    /// Whether the whole of `input` matches, like [`crate::RegExp::matches`].
+    pub fn is_match(&mut self, input: &str) -> bool {
+        let state = self.walk(input);
+        self.is_accept(state)
+    }
+
+    /// The state after `input` from the start state, or a dead state as soon as one is reached.
+    pub fn walk(&mut self, input: &str) -> usize {
+        let mut current = 0;
+        for ch in input.chars() {
+            current = self.next(current, ch);
+            if self.is_dead(current) {
+                break;
+            }
+        }
+        current
+    }
+
+    /// The NFA states that `state` stands for.
+    pub fn subset(&self, state: usize) -> &StateSet {
+        &self.cache.subsets[state]
+    }
+
+    /// Whether no input leads from `state` to a match.
+    pub fn is_dead(&self, state: usize) -> bool {
+        self.cache.subsets[state].is_empty()
+    }
+
+    pub fn is_accept(&self, state: usize) -> bool {
+        !self.cache.subsets[state].is_disjoint(self.accepts)
+    }
+
+    pub fn next(&mut self, state: usize, input: char) -> usize {
+        let cache = &mut self.cache;
+        if let Some(&next) = cache.map[state].get(&input) {
+            return next;
+        }
+        let mut subset = self.nfa.step(&cache.subsets[state], input);
+        if cache.unanchored {
+            subset.extend(cache.subsets[0].iter().copied());
+        }
+        let next = match cache.index.get(&subset) {
+            Some(&next) => next,
+            None => {
+                cache.index.insert(subset.clone(), cache.subsets.len());
+                cache.subsets.push(subset);
+                cache.map.push(HashMap::new());
+                cache.subsets.len() - 1
+            }
+        };
+        cache.map[state].insert(input, next);
+        next
+    }
+}
+
+impl LazyCache {
+    /// only the start state of `nfa`
+    fn new(nfa: &NFA, unanchored: bool) -> Self {
+        let start = nfa.start.unwrap_or_else(|| panic!("nfa.start is None"));
+        let start = nfa.epsilon_expand(BTreeSet::from([start]));
+        Self {
+            unanchored,
+            index: HashMap::from([(start.clone(), 0)]),
+            subsets: vec![start],
+            map: vec![HashMap::new()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod lazy_tests {
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    #[test]
+    fn builds_only_visited_states() {
+        let tokens = Lexer::new("(a|b)*abb".to_string()).tokenize();
+        let nfa = Parser::new(tokens).parse();
+        let mut dfa = LazyDFA::new(&nfa);
+
+        assert!(!dfa.is_match("aaaa"));
+        assert_eq!(dfa.cache.subsets.len(), 2);
+        assert!(dfa.is_match("babb"));
+        assert!(!dfa.is_match("abba"));
+        assert!(dfa.cache.subsets.len() <= nfa.nfa2dfa().states().count());
+    }
+
+    #[test]
+    fn unanchored_accepts_where_matches_end() {
+        let tokens = Lexer::new("ab".to_string()).tokenize();
+        let nfa = Parser::new(tokens).parse();
+        let mut dfa = LazyDFA::unanchored(&nfa);
+
+        let mut state = 0;
+        let mut ends = vec![];
+        for (i, ch) in "aabxab".chars().enumerate() {
+            state = dfa.next(state, ch);
+            if dfa.is_accept(state) {
+                ends.push(i + 1);
+            }
+        }
+        assert_eq!(ends, vec![3, 6]);
+    }
+}
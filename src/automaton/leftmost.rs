@@ -0,0 +1,264 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::meta::MatchKind;
+
+use super::{lazy::LazyDFA, nfa::NFA, State, StateSet};
+
+/// stands for a thread that has reached an accept state, like the Pike VM's match state
+const MATCH: usize = usize::MAX;
+
+/// A lazy DFA that finds where the leftmost-first match ends, by running the Pike VM without
+/// capture slots: each DFA state is the Pike VM's list of threads in priority order, along with
+/// whether new threads are still started (only until the first match is seen). Threads after a
+/// match have lower priority and are dropped, so once no thread before the match is left, the
+/// last match seen is the leftmost-first one.
+pub struct LeftmostDFA<'a> {
+    nfa: &'a NFA,
+    start: State,
+    accepts: &'a StateSet,
+    threads: Vec<Vec<usize>>,
+    seeding: Vec<bool>,
+    index: HashMap<(Vec<usize>, bool), usize>,
+    map: Vec<HashMap<char, usize>>,
+}
+
+impl<'a> LeftmostDFA<'a> {
+    pub fn new(nfa: &'a NFA) -> Self {
+        let start = nfa.start.unwrap_or_else(|| panic!("nfa.start is None"));
+        let accepts = nfa
+            .accepts
+            .as_ref()
+            .unwrap_or_else(|| panic!("nfa.accepts is None"));
+        let mut dfa = Self {
+            nfa,
+            start,
+            accepts,
+            threads: vec![],
+            seeding: vec![],
+            index: HashMap::new(),
+            map: vec![],
+        };
+        let mut threads = vec![];
+        dfa.closure(start.id, &mut threads, &mut HashSet::new());
+        dfa.intern(threads, true);
+        dfa
+    }
+
+    /// End of the leftmost-first match that starts at byte offset `at` or later.
+    pub fn find_end(&mut self, haystack: &str, at: usize) -> Option<usize> {
+        let mut state = 0;
+        let mut end = None;
+        let mut pos = at;
+        loop {
+            let matched = self.threads[state].contains(&MATCH);
+            if matched {
+                end = Some(pos);
+            }
+            let alive = self.threads[state].first().is_some_and(|&t| t != MATCH);
+            if !alive && (matched || !self.seeding[state]) {
+                break;
+            }
+            let Some(ch) = haystack[pos..].chars().next() else {
+                break;
+            };
+            state = self.next(state, ch);
+            pos += ch.len_utf8();
+        }
+        end
+    }
+
+    fn next(&mut self, state: usize, input: char) -> usize {
+        if let Some(&next) = self.map[state].get(&input) {
+            return next;
+        }
+        let mut threads = vec![];
+        let mut seen = HashSet::new();
+        for &thread in &self.threads[state] {
+            if thread == MATCH {
+                break;
+            }
+            if let Some(targets) = self.nfa.targets(State::new(thread), Some(input)) {
+                for target in targets {
+                    self.closure(target.id, &mut threads, &mut seen);
+                }
+            }
+        }
+        let seeding = self.seeding[state] && !self.threads[state].contains(&MATCH);
+        if seeding {
+            self.closure(self.start.id, &mut threads, &mut seen);
+        }
+
+        let next = self.intern(threads, seeding);
+        self.map[state].insert(input, next);
+        next
+    }
+
+    /// add `state` and its epsilon closure to `threads` in priority order
+    fn closure(&self, state: usize, threads: &mut Vec<usize>, seen: &mut HashSet<usize>) {
+        let mut stack = vec![Frame::Explore(state)];
+        while let Some(frame) = stack.pop() {
+            let state = match frame {
+                Frame::Explore(state) => state,
+                Frame::Match => {
+                    if seen.insert(MATCH) {
+                        threads.push(MATCH);
+                    }
+                    continue;
+                }
+            };
+            if !seen.insert(state) {
+                continue;
+            }
+            threads.push(state);
+            // popped in priority order: the epsilon edges first, then the match
+            if self.accepts.contains(&State::new(state)) {
+                stack.push(Frame::Match);
+            }
+            if let Some(targets) = self.nfa.targets(State::new(state), None) {
+                stack.extend(targets.iter().rev().map(|target| Frame::Explore(target.id)));
+            }
+        }
+    }
+
+    fn intern(&mut self, threads: Vec<usize>, seeding: bool) -> usize {
+        let key = (threads, seeding);
+        if let Some(&state) = self.index.get(&key) {
+            return state;
+        }
+        self.index.insert(key.clone(), self.threads.len());
+        self.threads.push(key.0);
+        self.seeding.push(key.1);
+        self.map.push(HashMap::new());
+        self.threads.len() - 1
+    }
+}
+
+/// a step of [`LeftmostDFA::closure`]: visit a state, or add the match after the states reached
+/// from an accept state
+#[derive(Debug, Clone, Copy)]
+enum Frame {
+    Explore(usize),
+    Match,
+}
+
+/// Finds match spans without simulating the NFA: [`LeftmostDFA`] finds where the leftmost-first
+/// match ends, then a lazy DFA of the reversed pattern runs backwards from there to the leftmost
+/// offset where a match ending there can start. That is the leftmost start of any match, so for
+/// leftmost-longest matches a forward lazy DFA then finds the longest match from it.
+pub struct SpanSearcher<'a> {
+    forward: LeftmostDFA<'a>,
+    reverse: LazyDFA<'a>,
+    longest: Option<LazyDFA<'a>>,
+}
+
+impl<'a> SpanSearcher<'a> {
+    /// `reverse` has to be `nfa.reverse()`.
+    pub fn new(nfa: &'a NFA, reverse: &'a NFA, kind: MatchKind) -> Self {
+        Self {
+            forward: LeftmostDFA::new(nfa),
+            reverse: LazyDFA::new(reverse),
+            longest: (kind == MatchKind::LeftmostLongest).then(|| LazyDFA::new(nfa)),
+        }
+    }
+
+    /// The leftmost match starting at byte offset `at` or later, as a byte range.
+    pub fn find_at(&mut self, haystack: &str, at: usize) -> Option<(usize, usize)> {
+        let end = self.forward.find_end(haystack, at)?;
+
+        let mut state = 0;
+        let mut start = self.reverse.is_accept(state).then_some(end);
+        for (offset, ch) in haystack[at..end].char_indices().rev() {
+            state = self.reverse.next(state, ch);
+            if self.reverse.is_dead(state) {
+                break;
+            }
+            if self.reverse.is_accept(state) {
+                start = Some(at + offset);
+            }
+        }
+        // the match that ends at `end` starts at `at` or later, so the reversed pattern reaches it
+        debug_assert!(
+            start.is_some(),
+            "no match ending at {} starts after {}",
+            end,
+            at
+        );
+        let start = start?;
+
+        let Some(longest) = &mut self.longest else {
+            return Some((start, end));
+        };
+        let mut state = 0;
+        let mut end = start;
+        for (offset, ch) in haystack[start..].char_indices() {
+            state = longest.next(state, ch);
+            if longest.is_dead(state) {
+                break;
+            }
+            if longest.is_accept(state) {
+                end = start + offset + ch.len_utf8();
+            }
+        }
+        Some((start, end))
+    }
+}
+
+#[cfg(test)]
+mod leftmost_tests {
+    use crate::automaton::pikevm::PikeVM;
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    #[test]
+    fn agrees_with_pikevm() {
+        let cases = [
+            ("a|ab", "xxab"),
+            ("ab|a", "xxab"),
+            ("(a|b)*c", "abxbabcc"),
+            ("b*", "aab"),
+            ("(ab|a)(bc|c)*", "xabcbc"),
+            ("z", "abc"),
+            ("\\é(x|\\ü)*", "aéüxü"),
+        ];
+        for kind in [MatchKind::LeftmostFirst, MatchKind::LeftmostLongest] {
+            for (regex, haystack) in cases {
+                let tokens = Lexer::new(regex.to_string()).tokenize();
+                let nfa = Parser::new(tokens).parse();
+                let reverse = nfa.reverse();
+                let mut searcher = SpanSearcher::new(&nfa, &reverse, kind);
+                let expected = PikeVM::new(&nfa, kind)
+                    .search(haystack, 0, false)
+                    .map(|slots| (slots[0].unwrap(), slots[1].unwrap()));
+                assert_eq!(
+                    searcher.find_at(haystack, 0),
+                    expected,
+                    "{kind:?} {regex} on {haystack}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn long_epsilon_chain() {
+        use crate::compiler::fragment::{NFAFragment, NFAInput};
+
+        // 0 -ε-> 1 -ε-> ... -ε-> n -a-> n + 1, deeper than a recursive closure could go
+        let n = 200_000;
+        let mut fragment = NFAFragment::new(State::new(0), [State::new(n + 1)].into(), None);
+        for i in 0..n {
+            fragment.connect(
+                NFAInput::new("".to_string(), State::new(i)),
+                State::new(i + 1),
+            );
+        }
+        fragment.connect(
+            NFAInput::new("a".to_string(), State::new(n)),
+            State::new(n + 1),
+        );
+        let nfa = fragment.build();
+        let reverse = nfa.reverse();
+        let mut searcher = SpanSearcher::new(&nfa, &reverse, MatchKind::LeftmostFirst);
+        assert_eq!(searcher.find_at("xa", 0), Some((1, 2)));
+    }
+}
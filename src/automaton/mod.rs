@@ -1,8 +1,17 @@
 use std::collections::BTreeSet;
 
+pub mod aho_corasick;
+pub mod count;
+pub mod dense;
 pub mod dfa;
+pub mod eliminate;
+pub mod generate;
+pub mod lazy;
+pub mod leftmost;
 pub mod nfa;
+pub mod pikevm;
 pub mod runtime;
+pub mod utf8;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct State {
@@ -1,32 +1,156 @@
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use crate::compiler::fragment::NFAInput;
 
 use super::{dfa::DFA, State, StateSet};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub struct NFA {
     pub start: Option<State>,
     pub accepts: Option<StateSet>,
     pub map: Option<HashMap<NFAInput, StateSet>>,
+    /// capture slots of group boundary states, see [`crate::compiler::Context::slots`]
+    pub slots: HashMap<State, usize>,
 }
 
 impl NFA {
     fn transition(&self, input: &NFAInput) -> Option<StateSet> {
-        if let Some(states) = self.map.as_ref().unwrap().get(input) {
-            Some(states.clone())
-        } else {
-            None
+        self.map.as_ref().unwrap().get(input).cloned()
+    }
+
+    /// every non-epsilon input label, in ascending order
+    pub fn alphabet(&self) -> BTreeSet<char> {
+        self.map
+            .as_ref()
+            .unwrap_or_else(|| panic!("self.map is None"))
+            .keys()
+            .filter_map(|input| input.input.chars().next())
+            .collect()
+    }
+
+    /// targets of the transitions from `state` on `input`, or on epsilon if `None`
+    pub fn targets(&self, state: State, input: Option<char>) -> Option<&StateSet> {
+        let input = input.map(String::from).unwrap_or_default();
+        self.map.as_ref().unwrap().get(&NFAInput::new(input, state))
+    }
+
+    /// NFA for the reversed language: every edge turned around, starting from a new state with
+    /// epsilon edges to the old accept states and accepting in the old start state
+    pub fn reverse(&self) -> NFA {
+        let map = self
+            .map
+            .as_ref()
+            .unwrap_or_else(|| panic!("self.map is None"));
+        let start = self.start.unwrap_or_else(|| panic!("self.start is None"));
+        let accepts = self
+            .accepts
+            .as_ref()
+            .unwrap_or_else(|| panic!("self.accepts is None"));
+
+        let mut reversed = HashMap::<NFAInput, StateSet>::new();
+        for (input, targets) in map {
+            for target in targets {
+                reversed
+                    .entry(NFAInput::new(input.input.clone(), *target))
+                    .or_default()
+                    .insert(input.current_state);
+            }
+        }
+        let new_start = self.fresh_state();
+        reversed.insert(NFAInput::new("".to_string(), new_start), accepts.clone());
+
+        NFA {
+            start: Some(new_start),
+            accepts: Some(BTreeSet::from([start])),
+            map: Some(reversed),
+            slots: HashMap::new(),
         }
     }
 
+    /// states from which some accept state can be reached
+    pub fn live_states(&self) -> StateSet {
+        let map = self
+            .map
+            .as_ref()
+            .unwrap_or_else(|| panic!("self.map is None"));
+        let mut sources = HashMap::<State, Vec<State>>::new();
+        for (input, targets) in map {
+            for target in targets {
+                sources
+                    .entry(*target)
+                    .or_default()
+                    .push(input.current_state);
+            }
+        }
+        let mut live = self
+            .accepts
+            .clone()
+            .unwrap_or_else(|| panic!("self.accepts is None"));
+        let mut stack: Vec<State> = live.iter().copied().collect();
+        while let Some(state) = stack.pop() {
+            for &source in sources.get(&state).into_iter().flatten() {
+                if live.insert(source) {
+                    stack.push(source);
+                }
+            }
+        }
+        live
+    }
+
+    /// NFA for the same language without the empty string: a new start state with the
+    /// character edges out of the old start's epsilon closure, which no edge leads back to
+    pub fn non_empty(&self) -> NFA {
+        let start = self.start.unwrap_or_else(|| panic!("self.start is None"));
+        let closure = self.epsilon_expand(BTreeSet::from([start]));
+        let new_start = self.fresh_state();
+
+        let mut map = self
+            .map
+            .clone()
+            .unwrap_or_else(|| panic!("self.map is None"));
+        let mut edges = HashMap::<NFAInput, StateSet>::new();
+        for (input, targets) in &map {
+            if !input.input.is_empty() && closure.contains(&input.current_state) {
+                edges
+                    .entry(NFAInput::new(input.input.clone(), new_start))
+                    .or_default()
+                    .extend(targets);
+            }
+        }
+        map.extend(edges);
+
+        NFA {
+            start: Some(new_start),
+            accepts: self.accepts.clone(),
+            map: Some(map),
+            slots: HashMap::new(),
+        }
+    }
+
+    /// a state id above every state of the NFA
+    fn fresh_state(&self) -> State {
+        let map = self
+            .map
+            .as_ref()
+            .unwrap_or_else(|| panic!("self.map is None"));
+        map.iter()
+            .flat_map(|(input, targets)| targets.iter().chain([&input.current_state]))
+            .chain(self.accepts.iter().flatten())
+            .chain(self.start.as_ref())
+            .map(|state| state.id + 1)
+            .max()
+            .map(State::new)
+            .unwrap_or(State::new(0))
+    }
+
     pub fn epsilon_expand(&self, states: StateSet) -> StateSet {
         let mut que = BTreeSet::<State>::new();
         que.extend(states);
         let mut done = BTreeSet::<State>::new();
 
         while !que.is_empty() {
-            let state = que.iter().next().unwrap().clone();
+            let state = *que.iter().next().unwrap();
             que.remove(&state);
             done.insert(state);
 
@@ -43,29 +167,74 @@ impl NFA {
         done
     }
 
-    pub fn nfa2dfa(&self) -> DFA {
-        let transition = |prev_states: &StateSet, input: String| {
-            let mut new_states = BTreeSet::<State>::new();
-            for state in prev_states {
-                let next_states = self.transition(&NFAInput::new(input.clone(), state.clone()));
-                if let Some(states) = next_states {
-                    new_states.extend(states);
-                }
+    /// states reachable from `states` by consuming `input`, including their epsilon closure
+    pub fn step(&self, states: &StateSet, input: char) -> StateSet {
+        let mut new_states = BTreeSet::<State>::new();
+        for state in states {
+            let next_states = self.transition(&NFAInput::new(input.to_string(), *state));
+            if let Some(states) = next_states {
+                new_states.extend(states);
             }
-            self.epsilon_expand(new_states)
-        };
+        }
+        self.epsilon_expand(new_states)
+    }
+
+    /// materialize a DFA by subset construction
+    pub fn nfa2dfa(&self) -> DFA {
+        self.nfa2dfa_limited(usize::MAX).unwrap()
+    }
+
+    /// like [`NFA::nfa2dfa`], but give up once there would be more than `limit` states
+    pub fn nfa2dfa_limited(&self, limit: usize) -> Option<DFA> {
+        let alphabet = self.alphabet();
+        let nfa_accepts = self
+            .accepts
+            .clone()
+            .unwrap_or_else(|| panic!("self.accepts is None"));
 
         let mut tmp = BTreeSet::new();
         tmp.insert(self.start.unwrap_or_else(|| panic!("self.start is None")));
         let dfa_start = self.epsilon_expand(tmp);
 
-        DFA {
-            start: dfa_start,
-            accepts: self
-                .accepts
-                .clone()
-                .unwrap_or_else(|| panic!("self.accepts is None")),
-            transition: Box::new(transition),
+        let mut index = HashMap::<StateSet, State>::new();
+        let mut subsets = vec![];
+        let mut map = vec![];
+        index.insert(dfa_start.clone(), State::new(0));
+        subsets.push(dfa_start);
+
+        let mut current = 0;
+        while current < subsets.len() {
+            let mut transitions = BTreeMap::new();
+            for &input in &alphabet {
+                let next = self.step(&subsets[current], input);
+                if next.is_empty() {
+                    continue;
+                }
+                let next_state = *index.entry(next.clone()).or_insert_with(|| {
+                    subsets.push(next);
+                    State::new(subsets.len() - 1)
+                });
+                transitions.insert(input, next_state);
+            }
+            if subsets.len() > limit {
+                return None;
+            }
+            map.push(transitions);
+            current += 1;
         }
+
+        let accepts = subsets
+            .iter()
+            .enumerate()
+            .filter(|(_, subset)| !subset.is_disjoint(&nfa_accepts))
+            .map(|(id, _)| State::new(id))
+            .collect();
+
+        Some(DFA {
+            start: State::new(0),
+            accepts,
+            subsets,
+            map,
+        })
     }
 }
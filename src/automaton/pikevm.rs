@@ -0,0 +1,427 @@
+use std::cmp::{Ordering, Reverse};
+use std::mem;
+
+use crate::compiler::fragment::NFAInput;
+use crate::meta::MatchKind;
+
+use super::{nfa::NFA, State};
+
+/// Simulates an NFA over the input with one thread per NFA state (Pike's VM), in O(n·m) time for
+/// an input of length n and m states, while tracking capture slots.
+///
+/// Threads are kept in priority order, which yields leftmost-first matches: a thread that
+/// reaches an accept state cuts off every thread of lower priority. Epsilon edges are followed
+/// in ascending state order, and the fragment construction numbers states so that this is the
+/// preferred order: the left side of `|` first and another iteration of `*` before leaving it.
+/// Stopping at an accept state ranks below all of its epsilon edges, so that `a*` keeps going.
+///
+/// For leftmost-longest matches, priority does not matter. Instead, when two threads meet in
+/// the same state, the one whose capture slots are better by POSIX rules survives, and a match
+/// does not cut off other threads but has to be longer than the previous one.
+#[derive(Debug, Clone)]
+pub struct PikeVM {
+    start: usize,
+    accepts: Vec<bool>,
+    /// epsilon targets of each state, in priority order
+    epsilons: Vec<Vec<usize>>,
+    /// character transitions of each state
+    transitions: Vec<Vec<(char, Vec<usize>)>>,
+    /// capture slot recorded on entering each state
+    slots: Vec<Option<usize>>,
+    slot_len: usize,
+    kind: MatchKind,
+}
+
+impl PikeVM {
+    pub fn new(nfa: &NFA, kind: MatchKind) -> Self {
+        let map = nfa
+            .map
+            .as_ref()
+            .unwrap_or_else(|| panic!("nfa.map is None"));
+        let start = nfa.start.unwrap_or_else(|| panic!("nfa.start is None"));
+        let accepts = nfa
+            .accepts
+            .as_ref()
+            .unwrap_or_else(|| panic!("nfa.accepts is None"));
+
+        let len = map
+            .iter()
+            .flat_map(|(input, targets)| targets.iter().chain([&input.current_state]))
+            .chain(accepts)
+            .chain([&start])
+            .map(|state| state.id + 1)
+            .max()
+            .unwrap_or(0);
+
+        let mut epsilons = vec![vec![]; len];
+        let mut transitions = vec![vec![]; len];
+        for (
+            NFAInput {
+                input,
+                current_state,
+            },
+            targets,
+        ) in map
+        {
+            let targets: Vec<usize> = targets.iter().map(|state| state.id).collect();
+            match input.chars().next() {
+                None => epsilons[current_state.id] = targets,
+                Some(ch) => transitions[current_state.id].push((ch, targets)),
+            }
+        }
+
+        let mut slots = vec![None; len];
+        for (state, slot) in &nfa.slots {
+            slots[state.id] = Some(*slot);
+        }
+        // slots 0 and 1 hold the bounds of the whole match
+        let slot_len = nfa
+            .slots
+            .values()
+            .map(|slot| slot + 1)
+            .max()
+            .unwrap_or(0)
+            .max(2);
+
+        Self {
+            start: start.id,
+            accepts: (0..len)
+                .map(|id| accepts.contains(&State::new(id)))
+                .collect(),
+            epsilons,
+            transitions,
+            slots,
+            slot_len,
+            kind,
+        }
+    }
+
+    /// number of capture slots, two per group including the whole match as group 0
+    pub fn slot_len(&self) -> usize {
+        self.slot_len
+    }
+
+    /// Leftmost match in `haystack` starting at byte offset `at` or later, or exactly at
+    /// `at` if `anchored`. Returns the capture slots as byte offsets.
+    pub fn search(&self, haystack: &str, at: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        self.exec(haystack, at, anchored, false)
+    }
+
+    /// Whether the whole of `haystack` matches.
+    pub fn is_full_match(&self, haystack: &str) -> bool {
+        self.exec(haystack, 0, true, true).is_some()
+    }
+
+    /// shared by [`PikeVM::search`] and [`PikeVM::is_full_match`]; with `to_end`, only matches
+    /// ending at the end of `haystack` count
+    fn exec(
+        &self,
+        haystack: &str,
+        at: usize,
+        anchored: bool,
+        to_end: bool,
+    ) -> Option<Vec<Option<usize>>> {
+        let mut clist = Threads::new(self.match_state() + 1, self.slot_len);
+        let mut nlist = Threads::new(self.match_state() + 1, self.slot_len);
+        let mut scratch = vec![None; self.slot_len];
+        let mut stack = vec![];
+        let mut matched = None;
+
+        let mut pos = at;
+        loop {
+            // a new thread starting here has the lowest priority of all
+            if matched.is_none() && (!anchored || pos == at) {
+                scratch.fill(None);
+                scratch[0] = Some(pos);
+                self.add(&mut clist, &mut stack, self.start, pos, &mut scratch);
+            }
+            if clist.is_empty() {
+                break;
+            }
+
+            let ch = haystack[pos..].chars().next();
+            for i in 0..clist.len() {
+                let state = clist.dense[i];
+                if state == self.match_state() {
+                    if to_end && pos < haystack.len() {
+                        continue;
+                    }
+                    let mut slots = clist.slots(state).to_vec();
+                    slots[1] = Some(pos);
+                    if self.kind == MatchKind::LeftmostFirst {
+                        matched = Some(slots);
+                        break;
+                    }
+                    if matched
+                        .as_ref()
+                        .is_none_or(|best| posix_better(&slots, best))
+                    {
+                        matched = Some(slots);
+                    }
+                    continue;
+                }
+                let Some(ch) = ch else { continue };
+                for (label, targets) in &self.transitions[state] {
+                    if *label != ch {
+                        continue;
+                    }
+                    for &target in targets {
+                        scratch.copy_from_slice(clist.slots(state));
+                        let pos = pos + ch.len_utf8();
+                        self.add(&mut nlist, &mut stack, target, pos, &mut scratch);
+                    }
+                }
+            }
+
+            let Some(ch) = ch else { break };
+            pos += ch.len_utf8();
+            mem::swap(&mut clist, &mut nlist);
+            nlist.clear();
+        }
+        matched
+    }
+
+    /// virtual state that the accept states lead to, one past the NFA's states
+    fn match_state(&self) -> usize {
+        self.accepts.len()
+    }
+
+    /// add `state` and its epsilon closure to `threads` in priority order, recording capture
+    /// slots at `pos` on the way. For leftmost-longest matches, a thread already in `state` is
+    /// replaced if these slots are better. Walks depth-first with `stack` rather than recursing,
+    /// so that long chains of epsilon edges cannot overflow the call stack.
+    fn add(
+        &self,
+        threads: &mut Threads,
+        stack: &mut Vec<Frame>,
+        state: usize,
+        pos: usize,
+        slots: &mut [Option<usize>],
+    ) {
+        stack.push(Frame::Explore(state));
+        while let Some(frame) = stack.pop() {
+            let state = match frame {
+                Frame::Explore(state) => state,
+                Frame::Restore(slot, old) => {
+                    slots[slot] = old;
+                    continue;
+                }
+            };
+            if let Some(slot) = self.slots.get(state).copied().flatten() {
+                stack.push(Frame::Restore(slot, slots[slot].replace(pos)));
+            }
+
+            let visit = if threads.contains(state) {
+                self.kind == MatchKind::LeftmostLongest && posix_better(slots, threads.slots(state))
+            } else {
+                threads.insert(state);
+                true
+            };
+            if !visit {
+                continue;
+            }
+            threads.slots_mut(state).copy_from_slice(slots);
+            if state != self.match_state() {
+                // popped in priority order: the epsilon edges first, then the match
+                if self.accepts[state] {
+                    stack.push(Frame::Explore(self.match_state()));
+                }
+                for &next in self.epsilons[state].iter().rev() {
+                    stack.push(Frame::Explore(next));
+                }
+            }
+        }
+    }
+}
+
+/// a step of [`PikeVM::add`]: visit a state, or undo the capture slot set on visiting one
+#[derive(Debug, Clone, Copy)]
+enum Frame {
+    Explore(usize),
+    Restore(usize, Option<usize>),
+}
+
+/// Whether capture slots `a` are better than `b` by POSIX rules: comparing each group in turn,
+/// starting earlier is better, then ending later, and taking part at all is better than not.
+fn posix_better(a: &[Option<usize>], b: &[Option<usize>]) -> bool {
+    let key = |slots: &[Option<usize>]| {
+        slots
+            .chunks(2)
+            .map(|pair| {
+                (
+                    pair[0].is_some(),
+                    Reverse(pair[0]),
+                    pair[1].is_some(),
+                    pair[1],
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    key(a).cmp(&key(b)) == Ordering::Greater
+}
+
+/// Sparse set of states in insertion order, with the capture slots of each. Clearing and
+/// membership tests take constant time.
+struct Threads {
+    dense: Vec<usize>,
+    sparse: Vec<usize>,
+    slots: Vec<Option<usize>>,
+    slot_len: usize,
+}
+
+impl Threads {
+    fn new(states: usize, slot_len: usize) -> Self {
+        Self {
+            dense: Vec::with_capacity(states),
+            sparse: vec![0; states],
+            slots: vec![None; states * slot_len],
+            slot_len,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    fn contains(&self, state: usize) -> bool {
+        let index = self.sparse[state];
+        index < self.dense.len() && self.dense[index] == state
+    }
+
+    fn insert(&mut self, state: usize) {
+        self.sparse[state] = self.dense.len();
+        self.dense.push(state);
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+    }
+
+    fn slots(&self, state: usize) -> &[Option<usize>] {
+        &self.slots[state * self.slot_len..(state + 1) * self.slot_len]
+    }
+
+    fn slots_mut(&mut self, state: usize) -> &mut [Option<usize>] {
+        &mut self.slots[state * self.slot_len..(state + 1) * self.slot_len]
+    }
+}
+
+#[cfg(test)]
+mod pikevm_tests {
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    fn search(regex: &str, haystack: &str) -> Option<Vec<Option<usize>>> {
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        PikeVM::new(&Parser::new(tokens).parse(), MatchKind::LeftmostFirst)
+            .search(haystack, 0, false)
+    }
+
+    fn search_longest(regex: &str, haystack: &str) -> Option<Vec<Option<usize>>> {
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        PikeVM::new(&Parser::new(tokens).parse(), MatchKind::LeftmostLongest)
+            .search(haystack, 0, false)
+    }
+
+    #[test]
+    fn leftmost_first() {
+        assert_eq!(search("b", "abc"), Some(vec![Some(1), Some(2)]));
+        // the left alternative wins even though the right one is longer
+        assert_eq!(search("a|ab", "xab"), Some(vec![Some(1), Some(2)]));
+        assert_eq!(search("ab|a", "xab"), Some(vec![Some(1), Some(3)]));
+        // stars are greedy
+        assert_eq!(search("a*", "aab"), Some(vec![Some(0), Some(2)]));
+        assert_eq!(search("z", "abc"), None);
+    }
+
+    #[test]
+    fn full_match() {
+        let tokens = Lexer::new("a|ab".to_string()).tokenize();
+        let pikevm = PikeVM::new(&Parser::new(tokens).parse(), MatchKind::LeftmostFirst);
+        // the preferred `a` does not reach the end, but `ab` does
+        assert!(pikevm.is_full_match("ab"));
+        assert!(pikevm.is_full_match("a"));
+        assert!(!pikevm.is_full_match("abb"));
+        assert!(!pikevm.is_full_match("xab"));
+    }
+
+    #[test]
+    fn captures() {
+        assert_eq!(
+            search("(a|ab)(c|bcd)", "abcd"),
+            Some(vec![Some(0), Some(4), Some(0), Some(1), Some(1), Some(4)])
+        );
+        // the last iteration of a repeated group is reported
+        assert_eq!(
+            search("(ab|c)*", "abcab"),
+            Some(vec![Some(0), Some(5), Some(3), Some(5)])
+        );
+        assert_eq!(
+            search("x(y)*", "x"),
+            Some(vec![Some(0), Some(1), None, None])
+        );
+    }
+
+    #[test]
+    fn leftmost_longest() {
+        assert_eq!(search_longest("a|ab", "xab"), Some(vec![Some(1), Some(3)]));
+        // still leftmost before longest
+        assert_eq!(
+            search_longest("bc|abc*|c", "xbcabcc"),
+            Some(vec![Some(1), Some(3)])
+        );
+        assert_eq!(search_longest("z", "abc"), None);
+    }
+
+    #[test]
+    fn posix_submatches() {
+        // `abcd` either way, but the first group should be as long as possible
+        assert_eq!(
+            search_longest("(a|ab)(c|bcd)(d*)", "abcd"),
+            Some(vec![
+                Some(0),
+                Some(4),
+                Some(0),
+                Some(2),
+                Some(2),
+                Some(3),
+                Some(3),
+                Some(4)
+            ])
+        );
+        assert_eq!(
+            search_longest("(a*)(a*)", "aa"),
+            Some(vec![Some(0), Some(2), Some(0), Some(2), Some(2), Some(2)])
+        );
+        assert_eq!(
+            search_longest("(a|b)*", "ab"),
+            Some(vec![Some(0), Some(2), Some(1), Some(2)])
+        );
+    }
+
+    #[test]
+    fn long_epsilon_chain() {
+        use crate::compiler::fragment::NFAFragment;
+
+        // 0 -ε-> 1 -ε-> ... -ε-> n -a-> n + 1, deeper than a recursive closure could go
+        let n = 200_000;
+        let mut fragment = NFAFragment::new(State::new(0), [State::new(n + 1)].into(), None);
+        for i in 0..n {
+            fragment.connect(
+                NFAInput::new("".to_string(), State::new(i)),
+                State::new(i + 1),
+            );
+        }
+        fragment.connect(
+            NFAInput::new("a".to_string(), State::new(n)),
+            State::new(n + 1),
+        );
+        let pikevm = PikeVM::new(&fragment.build(), MatchKind::LeftmostFirst);
+        assert_eq!(pikevm.search("xa", 0, false), Some(vec![Some(1), Some(2)]));
+    }
+}
@@ -1,31 +1,85 @@
-use super::{dfa::DFA, StateSet};
+use super::{dfa::DFA, State};
 
+/// Walks a DFA one character at a time. Get one from [`crate::RegExp::runtime`].
 pub struct Runtime<'a> {
-    dfa: &'a DFA<'a>,
-    current_state: StateSet,
+    dfa: &'a DFA,
+    current_state: Option<State>,
 }
 
 impl<'a> Runtime<'a> {
     pub fn new(dfa: &'a DFA) -> Self {
         Self {
-            dfa: dfa,
-            current_state: dfa.start.clone(),
+            dfa,
+            current_state: Some(dfa.start),
         }
     }
 
+    /// Whether the whole of `input` matches. Starts over from the start state, and stops reading
+    /// as soon as no continuation can match.
     pub fn run(&mut self, input: String) -> bool {
+        self.reset();
         for c in input.chars() {
-            self.do_transition(c.to_string());
+            if self.is_dead() {
+                return false;
+            }
+            self.step(c);
         }
-        self.is_accept()
+        self.is_accepting()
     }
 
-    fn do_transition(&mut self, input: String) {
-        let next_state = (self.dfa.transition)(&self.current_state, input);
+    /// Go back to the start state.
+    pub fn reset(&mut self) {
+        self.current_state = Some(self.dfa.start);
+    }
+
+    /// Consume one character. Once dead, the runtime stays dead.
+    pub fn step(&mut self, input: char) {
+        let next_state = self
+            .current_state
+            .and_then(|state| self.dfa.next(state, input));
         self.current_state = next_state;
     }
 
-    fn is_accept(&self) -> bool {
-        self.dfa.accepts.intersection(&self.current_state).count() > 0
+    /// Whether the input consumed so far matches.
+    pub fn is_accepting(&self) -> bool {
+        self.current_state
+            .is_some_and(|state| self.dfa.is_accept(state))
+    }
+
+    /// Whether the input consumed so far leads to the dead state, which no further input leaves.
+    pub fn is_dead(&self) -> bool {
+        self.current_state.is_none()
+    }
+
+    /// The DFA state reached so far, `None` for the dead state.
+    pub fn current_state(&self) -> Option<State> {
+        self.current_state
+    }
+}
+
+#[cfg(test)]
+mod runtime_tests {
+    use crate::RegExp;
+
+    #[test]
+    fn step_by_step() {
+        let regexp = RegExp::new("ab*".to_string());
+        let mut runtime = regexp.runtime();
+        assert!(!runtime.is_accepting());
+        runtime.step('a');
+        assert!(runtime.is_accepting());
+        let after_a = runtime.current_state();
+        runtime.step('b');
+        assert!(runtime.is_accepting());
+        runtime.step('a');
+        assert!(runtime.is_dead() && !runtime.is_accepting());
+        runtime.step('b');
+        assert!(runtime.is_dead());
+
+        runtime.reset();
+        runtime.step('a');
+        assert_eq!(runtime.current_state(), after_a);
+        assert!(runtime.run("abbb".to_string()));
+        assert!(!runtime.run("ba".to_string()));
     }
 }
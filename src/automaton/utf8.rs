@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use crate::compiler::fragment::{NFAFragment, NFAInput};
+
+use super::{nfa::NFA, State, StateSet};
+
+/// Rewrite a character NFA into a fragment that reads UTF-8 encoded bytes. Every character edge
+/// is replaced by a chain of byte edges through fresh states.
+///
+/// Byte automata store each byte `b` as the label `char::from(b)`, i.e. in U+0000..=U+00FF, so
+/// that the rest of the machinery works on them unchanged.
+pub fn utf8_fragment(nfa: &NFA) -> NFAFragment {
+    let map = nfa
+        .map
+        .as_ref()
+        .unwrap_or_else(|| panic!("nfa.map is None"));
+    let mut next_id = max_state(nfa).map_or(0, |state| state.id + 1);
+
+    let mut byte_map = HashMap::<NFAInput, StateSet>::new();
+    for (input, targets) in map {
+        let Some(ch) = input.input.chars().next() else {
+            byte_map.insert(input.clone(), targets.clone());
+            continue;
+        };
+
+        let mut buf = [0; 4];
+        let bytes = ch.encode_utf8(&mut buf).as_bytes();
+        let mut from = input.current_state;
+        for &byte in &bytes[..bytes.len() - 1] {
+            let to = State::new(next_id);
+            next_id += 1;
+            byte_map
+                .entry(NFAInput::new(char::from(byte).to_string(), from))
+                .or_default()
+                .insert(to);
+            from = to;
+        }
+        byte_map
+            .entry(NFAInput::new(
+                char::from(bytes[bytes.len() - 1]).to_string(),
+                from,
+            ))
+            .or_default()
+            .extend(targets);
+    }
+
+    NFAFragment {
+        start: nfa.start,
+        accepts: nfa.accepts.clone(),
+        map: byte_map,
+    }
+}
+
+/// Reinterpret a character NFA as a byte fragment, where the character U+00XX stands for the raw
+/// byte 0xXX. Panics on characters beyond U+00FF, which have no byte to stand for.
+pub fn raw_byte_fragment(nfa: &NFA) -> NFAFragment {
+    for ch in nfa.alphabet() {
+        if u32::from(ch) > 0xFF {
+            panic!(
+                "{:?} does not fit in a byte; use \\xHH escapes with (?-u)",
+                ch
+            );
+        }
+    }
+    NFAFragment {
+        start: nfa.start,
+        accepts: nfa.accepts.clone(),
+        map: nfa.map.clone().unwrap_or_else(|| panic!("nfa.map is None")),
+    }
+}
+
+fn max_state(nfa: &NFA) -> Option<State> {
+    let map = nfa.map.as_ref()?;
+    map.iter()
+        .flat_map(|(input, targets)| targets.iter().chain([&input.current_state]))
+        .chain(nfa.accepts.iter().flatten())
+        .chain(nfa.start.iter())
+        .max()
+        .copied()
+}
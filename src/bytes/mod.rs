@@ -0,0 +1,70 @@
+//! Matching over arbitrary bytes instead of `String`s.
+//!
+//! By default a pattern describes Unicode text, and each character is matched as its UTF-8
+//! encoding, so `é` matches the bytes `C3 A9`. Starting the pattern with `(?-u)` switches to raw
+//! bytes: every character must then be at most U+00FF and matches that single byte, which
+//! makes `\xFF` match the byte `FF` rather than the encoding of `ÿ`.
+//!
+//! `\xHH` itself is not specific to this module: every pattern, for [`crate::RegExp`] too, reads
+//! it as the character U+00HH and rejects `\x` without two hex digits after it. Only `(?-u)` is,
+//! and only at the very start of a pattern given to [`RegExp::new`] here.
+
+use crate::automaton::{
+    dense::DenseDFA,
+    utf8::{raw_byte_fragment, utf8_fragment},
+};
+use crate::compiler::{lexer::Lexer, parser::Parser};
+
+pub struct RegExp {
+    dfa: DenseDFA,
+}
+
+impl RegExp {
+    pub fn new(regex: &str) -> Self {
+        let (raw, regex) = match regex.strip_prefix("(?-u)") {
+            Some(rest) => (true, rest),
+            None => (false, regex),
+        };
+
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        let nfa = Parser::new(tokens).parse();
+        let fragment = if raw {
+            raw_byte_fragment(&nfa)
+        } else {
+            utf8_fragment(&nfa)
+        };
+        let dfa = fragment.build().nfa2dfa().minimize();
+        Self {
+            dfa: DenseDFA::new(&dfa, fragment.byte_classes()),
+        }
+    }
+
+    /// Whether the whole of `input` matches, like [`crate::RegExp::matches`].
+    pub fn is_match(&self, input: &[u8]) -> bool {
+        self.dfa.is_match(input)
+    }
+}
+
+#[cfg(test)]
+mod bytes_tests {
+    use super::*;
+
+    #[test]
+    fn unicode_mode_matches_utf8() {
+        // `é` and `ÿ` share their first byte, C3
+        let regexp = RegExp::new("(\\é|\\xff)*a");
+        assert!(regexp.is_match("éÿa".as_bytes()));
+        assert!(regexp.is_match(b"\xC3\xBFa"));
+        assert!(!regexp.is_match(b"\xC3a"));
+        assert!(!regexp.is_match(b"\xFFa"));
+        assert!(RegExp::new("\\é\\xff").is_match("éÿ".as_bytes()));
+        assert!(crate::RegExp::new("x\\x78".to_string()).matches("xx".to_string()));
+    }
+
+    #[test]
+    fn raw_mode_matches_bytes() {
+        let regexp = RegExp::new("(?-u)\\x00(\\xff)*");
+        assert!(regexp.is_match(b"\x00\xFF\xFF"));
+        assert!(!regexp.is_match(b"\x00\xC3\xBF"));
+    }
+}
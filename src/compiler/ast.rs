@@ -1,7 +1,13 @@
-use std::collections::BTreeSet;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use crate::syntax::{Ast, AstKind};
 
 use super::{
     fragment::{NFAFragment, NFAInput},
+    token::is_plain_char,
     Context,
 };
 
@@ -17,12 +23,13 @@ pub enum NodeKind {
     Star,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum NewNode {
     Char(CharacterNode),
     Union(UnionNode),
     Concat(ConcatNode),
     Star(StarNode),
+    Group(GroupNode),
 }
 
 impl Interpreter for NewNode {
@@ -32,8 +39,64 @@ impl Interpreter for NewNode {
             NewNode::Union(node) => node.assemble(ctx),
             NewNode::Concat(node) => node.assemble(ctx),
             NewNode::Star(node) => node.assemble(ctx),
+            NewNode::Group(node) => node.assemble(ctx),
+        }
+    }
+}
+
+/// Lower the public syntax tree: capturing groups are numbered in the order of their opening
+/// parentheses, other parentheses only matter for parsing, and unions and concatenations nest to
+/// the right as the parser used to build them. A union without alternatives matches nothing,
+/// which no node stands for, so it lowers to `None`, as does anything that has to match it; a
+/// concatenation without items is the empty string.
+pub fn lower(ast: &Ast) -> Option<NewNode> {
+    fn lower(ast: &Ast, groups: &mut usize) -> Option<NewNode> {
+        let nest = |kind: NodeKind, nodes: Vec<NewNode>| {
+            nodes
+                .into_iter()
+                .rev()
+                .reduce(|right, left| NewNode::new(kind.clone(), None, Some(left), Some(right)))
+        };
+        let empty = || NewNode::new(NodeKind::Char, Some("".to_string()), None, None);
+        // every operand is lowered, even after one that matches nothing, to number the groups
+        let mut operands = |asts: &[Ast]| -> Vec<Option<NewNode>> {
+            asts.iter().map(|ast| lower(ast, groups)).collect()
+        };
+        match &ast.kind {
+            AstKind::Empty => Some(empty()),
+            AstKind::Literal(ch) => Some(NewNode::new(
+                NodeKind::Char,
+                Some(ch.to_string()),
+                None,
+                None,
+            )),
+            AstKind::Union(asts) => {
+                let nodes = operands(asts).into_iter().flatten().collect();
+                nest(NodeKind::Union, nodes)
+            }
+            AstKind::Concat(asts) => {
+                let nodes = operands(asts).into_iter().collect::<Option<_>>()?;
+                Some(nest(NodeKind::Concat, nodes).unwrap_or_else(empty))
+            }
+            AstKind::Star(ast) => Some(match lower(ast, groups) {
+                Some(node) => NewNode::new(NodeKind::Star, None, Some(node), None),
+                None => empty(),
+            }),
+            AstKind::Group(ast) => group(None, ast, groups),
+            AstKind::NamedGroup(name, ast) => group(Some(name.clone()), ast, groups),
+            AstKind::NonCapturing(ast) => lower(ast, groups),
         }
     }
+    fn group(name: Option<String>, ast: &Ast, groups: &mut usize) -> Option<NewNode> {
+        *groups += 1;
+        let index = *groups;
+        Some(NewNode::Group(GroupNode {
+            index,
+            name,
+            origin: Box::new(lower(ast, groups)?),
+        }))
+    }
+    lower(ast, &mut 0)
 }
 
 impl NewNode {
@@ -75,7 +138,107 @@ impl NewNode {
     }
 }
 
-#[derive(Debug, Clone)]
+impl NewNode {
+    /// binding strength: `Union` < `Concat` < `Star` < `Char` and `Group`. The empty string has
+    /// to be spelled `(?:)` wherever it is not a whole alternative, so it binds like a union.
+    fn precedence(&self) -> u8 {
+        match self {
+            NewNode::Union(_) => 0,
+            NewNode::Char(node) if node.ch.is_empty() => 0,
+            NewNode::Concat(_) => 1,
+            NewNode::Star(_) => 2,
+            NewNode::Char(_) | NewNode::Group(_) => 3,
+        }
+    }
+
+    /// whether this is the empty string
+    pub fn is_empty(&self) -> bool {
+        matches!(self, NewNode::Char(node) if node.ch.is_empty())
+    }
+
+    /// the index of each named group. The parser rejects a name used twice; in a tree built
+    /// otherwise the first group with the name wins.
+    pub fn group_names(&self) -> HashMap<String, usize> {
+        fn collect(node: &NewNode, names: &mut HashMap<String, usize>) {
+            match node {
+                NewNode::Char(_) => {}
+                NewNode::Union(node) => {
+                    collect(&node.left, names);
+                    collect(&node.right, names);
+                }
+                NewNode::Concat(node) => {
+                    collect(&node.left, names);
+                    collect(&node.right, names);
+                }
+                NewNode::Star(node) => collect(&node.origin, names),
+                NewNode::Group(node) => {
+                    if let Some(name) = &node.name {
+                        names.entry(name.clone()).or_insert(node.index);
+                    }
+                    collect(&node.origin, names);
+                }
+            }
+        }
+        let mut names = HashMap::new();
+        collect(self, &mut names);
+        names
+    }
+
+    /// print `node`, parenthesized if it binds looser than `precedence`. These parentheses must
+    /// not capture.
+    fn fmt_operand(node: &NewNode, precedence: u8, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if node.precedence() < precedence {
+            write!(f, "(?:{})", node)
+        } else {
+            write!(f, "{}", node)
+        }
+    }
+}
+
+/// Prints the node as a pattern that parses back into the same tree, with as few parentheses as
+/// possible. The parser nests `|` and concatenation to the right, so only a left operand of the
+/// same kind needs parentheses.
+impl fmt::Display for NewNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NewNode::Char(node) => {
+                for c in node.ch.chars() {
+                    if !is_plain_char(c) {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                Ok(())
+            }
+            // an empty alternative is written as nothing at all
+            NewNode::Union(node) => {
+                if !node.left.is_empty() {
+                    Self::fmt_operand(&node.left, 1, f)?;
+                }
+                write!(f, "|")?;
+                if !node.right.is_empty() {
+                    Self::fmt_operand(&node.right, 0, f)?;
+                }
+                Ok(())
+            }
+            NewNode::Concat(node) => {
+                Self::fmt_operand(&node.left, 2, f)?;
+                Self::fmt_operand(&node.right, 1, f)
+            }
+            // `a**` does not parse, so a nested star needs parentheses as well
+            NewNode::Star(node) => {
+                Self::fmt_operand(&node.origin, 3, f)?;
+                write!(f, "*")
+            }
+            NewNode::Group(node) => match &node.name {
+                Some(name) => write!(f, "(?P<{}>{})", name, node.origin),
+                None => write!(f, "({})", node.origin),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CharacterNode {
     pub ch: String,
 }
@@ -85,14 +248,13 @@ impl Interpreter for CharacterNode {
         let start = context.new_state();
         let accept = context.new_state();
 
-        let mut fragment =
-            NFAFragment::new(start, BTreeSet::from_iter(vec![accept].into_iter()), None);
+        let mut fragment = NFAFragment::new(start, BTreeSet::from_iter(vec![accept]), None);
         fragment.connect(NFAInput::new(self.ch.clone(), start), accept);
         fragment
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UnionNode {
     pub left: Box<NewNode>,
     pub right: Box<NewNode>,
@@ -132,7 +294,7 @@ impl Interpreter for UnionNode {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ConcatNode {
     pub left: Box<NewNode>,
     pub right: Box<NewNode>,
@@ -167,7 +329,7 @@ impl Interpreter for ConcatNode {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StarNode {
     pub origin: Box<NewNode>,
 }
@@ -209,11 +371,50 @@ impl Interpreter for StarNode {
     }
 }
 
+/// capturing group number `index`, which records where its match starts and ends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupNode {
+    pub index: usize,
+    pub name: Option<String>,
+    pub origin: Box<NewNode>,
+}
+
+impl Interpreter for GroupNode {
+    fn assemble(&self, context: &mut Context) -> NFAFragment {
+        // created before the origin so that it sorts first among the alternatives it is part of
+        let start = context.new_state();
+        let origin = self.origin.assemble(context);
+        let accept = context.new_state();
+
+        let mut fragment = origin.new_skeleton();
+        fragment.start = Some(start);
+        fragment.accepts = Some(BTreeSet::from([accept]));
+        fragment.connect(
+            NFAInput::new("".to_string(), start),
+            origin
+                .start
+                .unwrap_or_else(|| panic!("origin.start is None")),
+        );
+        for state in origin
+            .accepts
+            .unwrap_or_else(|| panic!("origin.accepts is None"))
+        {
+            fragment.connect(NFAInput::new("".to_string(), state), accept);
+        }
+
+        context.slots.insert(start, self.index * 2);
+        context.slots.insert(accept, self.index * 2 + 1);
+        fragment
+    }
+}
+
 #[cfg(test)]
 mod ast_tests {
     use std::collections::HashMap;
 
     use crate::automaton::{State, StateSet};
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+    use crate::util::rng::Rng;
 
     use super::*;
 
@@ -259,4 +460,98 @@ mod ast_tests {
             }
         );
     }
+
+    fn parse(pattern: &str) -> NewNode {
+        let tokens = Lexer::new(pattern.to_string()).tokenize();
+        Parser::new(tokens).parse_node().unwrap()
+    }
+
+    fn random_node(rng: &mut Rng, depth: usize) -> NewNode {
+        const CHARS: [char; 8] = ['a', 'b', 'z', '|', '*', '(', ')', '\\'];
+        let pick = if depth == 0 { 0 } else { rng.next_u64() % 6 };
+        match pick {
+            0 => {
+                let ch = CHARS[(rng.next_u64() % CHARS.len() as u64) as usize];
+                NewNode::new(NodeKind::Char, Some(ch.to_string()), None, None)
+            }
+            1 => NewNode::new(NodeKind::Char, Some("".to_string()), None, None),
+            2 => NewNode::new(
+                NodeKind::Union,
+                None,
+                Some(random_node(rng, depth - 1)),
+                Some(random_node(rng, depth - 1)),
+            ),
+            3 => NewNode::new(
+                NodeKind::Concat,
+                None,
+                Some(random_node(rng, depth - 1)),
+                Some(random_node(rng, depth - 1)),
+            ),
+            4 => NewNode::new(
+                NodeKind::Star,
+                None,
+                Some(random_node(rng, depth - 1)),
+                None,
+            ),
+            // the parser renumbers groups anyway
+            _ => NewNode::Group(GroupNode {
+                index: 0,
+                name: None,
+                origin: Box::new(random_node(rng, depth - 1)),
+            }),
+        }
+    }
+
+    #[test]
+    fn display_minimal_parentheses() {
+        for pattern in [
+            "a|bc*",
+            "(a|b)c",
+            "a(b|c)",
+            "(ab)c",
+            "(a|b)|c",
+            "(ab)*",
+            "(a*)*",
+            "a|",
+            "(|a)b",
+            "a()",
+            "\\(\\*",
+            "(?:ab)c",
+            "(?:a|b)*",
+            "a(?:)",
+            "(?P<x>a|b)c",
+        ] {
+            assert_eq!(parse(pattern).to_string(), pattern);
+        }
+        assert_eq!(parse("(?:(?:a))(?:b)").to_string(), "ab");
+        assert_eq!(parse("(?<x_1>a)*").to_string(), "(?P<x_1>a)*");
+    }
+
+    #[test]
+    fn first_group_name_wins() {
+        use crate::syntax::{Ast, AstKind, Span};
+
+        let named = |ch| {
+            let literal = Ast::new(AstKind::Literal(ch), Span::new(0, 0));
+            Ast::new(
+                AstKind::NamedGroup("x".to_string(), Box::new(literal)),
+                Span::new(0, 0),
+            )
+        };
+        let ast = Ast::new(
+            AstKind::Concat(vec![named('a'), named('b')]),
+            Span::new(0, 0),
+        );
+        assert_eq!(lower(&ast).unwrap().group_names()["x"], 1);
+    }
+
+    #[test]
+    fn parse_print_parse_round_trip() {
+        let mut rng = Rng::new(7);
+        for _ in 0..500 {
+            let pattern = random_node(&mut rng, 5).to_string();
+            let node = parse(&pattern);
+            assert_eq!(parse(&node.to_string()), node, "pattern: {}", pattern);
+        }
+    }
 }
@@ -2,23 +2,13 @@ use std::collections::{BTreeSet, HashMap};
 
 use crate::automaton::{nfa::NFA, State, StateSet};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct NFAFragment {
     pub start: Option<State>,
     pub accepts: Option<StateSet>,
     pub map: HashMap<NFAInput, StateSet>,
 }
 
-impl Default for NFAFragment {
-    fn default() -> Self {
-        Self {
-            start: None,
-            accepts: None,
-            map: HashMap::new(),
-        }
-    }
-}
-
 impl NFAFragment {
     pub fn new(start: State, accepts: StateSet, map: Option<HashMap<NFAInput, StateSet>>) -> Self {
         if let Some(m) = map {
@@ -54,12 +44,16 @@ impl NFAFragment {
     }
 
     pub fn new_skeleton(&self) -> Self {
-        let new_fragment = NFAFragment {
+        NFAFragment {
             start: None,
             accepts: None,
             map: self.map.clone(),
-        };
-        new_fragment
+        }
+    }
+
+    /// byte classes of this fragment's transition labels, for a byte automaton
+    pub fn byte_classes(&self) -> ByteClasses {
+        ByteClasses::new(&self.map)
     }
 
     pub fn build(&self) -> NFA {
@@ -68,6 +62,7 @@ impl NFAFragment {
             start: self.start,
             accepts: self.accepts.clone(),
             map: Some(map),
+            slots: HashMap::new(),
         }
     }
 }
@@ -86,3 +81,58 @@ impl NFAInput {
         }
     }
 }
+
+/// Partition of the 256 byte values into classes whose members no transition tells apart.
+/// A byte automaton can index its transitions by class instead of by byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteClasses {
+    classes: [u8; 256],
+}
+
+impl ByteClasses {
+    /// Classes for a byte automaton, whose labels are the characters U+0000..=U+00FF. Each
+    /// label byte gets a class of its own and the runs of bytes between labels share one.
+    pub fn new(map: &HashMap<NFAInput, StateSet>) -> Self {
+        // boundaries[b] marks that a new class starts after byte b
+        let mut boundaries = [false; 256];
+        for input in map.keys() {
+            if let Some(ch) = input.input.chars().next() {
+                let byte = u8::try_from(u32::from(ch))
+                    .unwrap_or_else(|_| panic!("{:?} is not a byte label", ch));
+                if byte > 0 {
+                    boundaries[byte as usize - 1] = true;
+                }
+                boundaries[byte as usize] = true;
+            }
+        }
+
+        let mut classes = [0; 256];
+        let mut class = 0u8;
+        for byte in 0..256 {
+            classes[byte] = class;
+            if boundaries[byte] && byte < 255 {
+                class += 1;
+            }
+        }
+        Self { classes }
+    }
+
+    pub fn get(&self, byte: u8) -> u8 {
+        self.classes[byte as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes[255] as usize + 1
+    }
+
+    /// one byte of each class, in class order
+    pub fn representatives(&self) -> Vec<u8> {
+        let mut representatives = vec![];
+        for byte in 0..=255u8 {
+            if byte == 0 || self.get(byte) != self.get(byte - 1) {
+                representatives.push(byte);
+            }
+        }
+        representatives
+    }
+}
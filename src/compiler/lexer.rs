@@ -1,8 +1,15 @@
-use super::token::{Token, TokenKind};
+use crate::syntax::{Error, Span};
 
+use super::token::{is_plain_char, Token, TokenKind};
+
+/// Splits a pattern into tokens. A backslash makes the next character literal, except that
+/// `\xHH` stands for the character U+00HH in every pattern, with exactly two hex digits, so a
+/// literal `x` is written unescaped as `x` rather than `\x`. `(?!)` is a single token, for the
+/// pattern that matches nothing.
 #[derive(Debug, Clone)]
 pub struct Lexer {
     input: String,
+    /// byte offset of the next character
     pos: usize,
 }
 
@@ -11,34 +18,101 @@ impl Lexer {
         Self { input, pos: 0 }
     }
 
-    fn next(&mut self) -> Token {
-        let c = self.input.chars().nth(self.pos);
-        self.pos += 1;
-
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.input[self.pos..].chars().next();
         if let Some(ch) = c {
+            self.pos += ch.len_utf8();
+        }
+        c
+    }
+
+    /// an error about the pattern text from `start` to the current position
+    fn error(&self, message: String, start: usize) -> Error {
+        Error::new(message, Span::new(start, self.pos))
+    }
+
+    /// the two hex digits of `\xHH`, read as the character U+00HH
+    fn hex_escape(&mut self, start: usize) -> Result<char, Error> {
+        let digits: String = (0..2).filter_map(|_| self.next_char()).collect();
+        if digits.len() != 2 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error(format!("invalid escape: \\x{}", digits), start));
+        }
+        Ok(char::from(u8::from_str_radix(&digits, 16).unwrap()))
+    }
+
+    /// the name of `(?P<name>`, up to and including the `>`
+    fn group_name(&mut self, start: usize) -> Result<String, Error> {
+        let mut name = String::new();
+        loop {
+            match self.next_char() {
+                Some('>') if !name.is_empty() => return Ok(name),
+                Some(c) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+                _ => return Err(self.error(format!("invalid group name: {}", name), start)),
+            }
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        let start = self.pos;
+        let c = self.next_char();
+
+        let (kind, val) = if let Some(ch) = c {
             match ch {
-                'a'..='z' => Token::new(TokenKind::Char, Some(ch.to_string())),
-                '|' => Token::new(TokenKind::Union, None),
-                '*' => Token::new(TokenKind::Star, None),
-                '(' => Token::new(TokenKind::LParen, None),
-                ')' => Token::new(TokenKind::RParen, None),
-                _ => panic!("invalid character: {}", ch),
+                c if is_plain_char(c) => (TokenKind::Char, Some(ch.to_string())),
+                '\\' => {
+                    let Some(escaped) = self.next_char() else {
+                        let message = "pattern ends with an unfinished escape".to_string();
+                        return Err(self.error(message, start));
+                    };
+                    let ch = if escaped == 'x' {
+                        self.hex_escape(start)?
+                    } else {
+                        escaped
+                    };
+                    (TokenKind::Char, Some(ch.to_string()))
+                }
+                '|' => (TokenKind::Union, None),
+                '*' => (TokenKind::Star, None),
+                '(' if self.input[self.pos..].starts_with("?!)") => {
+                    self.pos += 3;
+                    (TokenKind::Never, None)
+                }
+                '(' if self.input[self.pos..].starts_with("?:") => {
+                    self.pos += 2;
+                    (TokenKind::LParenNonCapturing, None)
+                }
+                '(' if self.input[self.pos..].starts_with("?P<")
+                    || self.input[self.pos..].starts_with("?<") =>
+                {
+                    self.pos += self.input[self.pos..].find('<').unwrap() + 1;
+                    (TokenKind::LParenNamed, Some(self.group_name(start)?))
+                }
+                '(' => (TokenKind::LParen, None),
+                ')' => (TokenKind::RParen, None),
+                _ => return Err(self.error(format!("invalid character: {}", ch), start)),
             }
         } else {
-            Token::new(TokenKind::Eof, None)
-        }
+            (TokenKind::Eof, None)
+        };
+        Ok(Token::new(kind, val, Span::new(start, self.pos)))
     }
 
     pub fn tokenize(&mut self) -> Vec<Token> {
+        self.try_tokenize()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// like [`Lexer::tokenize`], returning the first error instead of panicking
+    pub fn try_tokenize(&mut self) -> Result<Vec<Token>, Error> {
         let mut tokens = vec![];
         loop {
-            let token = self.next();
-            if &token.kind == &TokenKind::Eof {
+            let token = self.next()?;
+            if token.kind == TokenKind::Eof {
                 tokens.push(token);
                 break;
             }
             tokens.push(token);
         }
-        tokens
+        Ok(tokens)
     }
 }
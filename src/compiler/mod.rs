@@ -1,19 +1,26 @@
-use crate::automaton::State;
+use std::collections::HashMap;
+
+use crate::automaton::{nfa::NFA, State, StateSet};
+
+use self::{
+    ast::{Interpreter, NewNode, NodeKind},
+    fragment::{NFAFragment, NFAInput},
+    simplify::simplify,
+};
 
 pub mod ast;
 pub mod fragment;
 pub mod lexer;
 pub mod parser;
+pub mod simplify;
 pub mod token;
 
+#[derive(Default)]
 pub struct Context {
     pub current_state: usize,
-}
-
-impl Default for Context {
-    fn default() -> Self {
-        Self { current_state: 0 }
-    }
+    /// capture slot recorded on entering a state: `2 * i` where group `i` starts and `2 * i + 1`
+    /// where it ends
+    pub slots: HashMap<State, usize>,
 }
 
 impl Context {
@@ -22,3 +29,54 @@ impl Context {
         State::new(self.current_state - 1)
     }
 }
+
+/// simplify `node` and assemble it into an NFA
+pub fn compile(node: NewNode) -> NFA {
+    let mut context = Context::default();
+    let fragment = simplify(node).assemble(&mut context);
+    let mut nfa = fragment.build();
+    nfa.slots = context.slots;
+    nfa
+}
+
+/// like [`compile`], for a pattern that may match nothing at all: `None` gives the NFA of the
+/// empty string without its accept state
+pub fn compile_lowered(node: Option<NewNode>) -> NFA {
+    match node {
+        Some(node) => compile(node),
+        None => {
+            let mut nfa = compile(NewNode::new(
+                NodeKind::Char,
+                Some("".to_string()),
+                None,
+                None,
+            ));
+            nfa.accepts = Some(StateSet::new());
+            nfa
+        }
+    }
+}
+
+/// Assemble several patterns into one NFA whose start state has an epsilon edge to each of them,
+/// along with the accept states of each pattern. A pattern that matches nothing is `None` and has
+/// no accept states. Groups do not capture.
+pub fn compile_set(nodes: Vec<Option<NewNode>>) -> (NFA, Vec<StateSet>) {
+    let mut context = Context::default();
+    let start = context.new_state();
+    let mut fragment = NFAFragment::default();
+    let mut accepts = vec![];
+    for node in nodes {
+        // a pattern that matches nothing has no accept states to reach
+        let Some(node) = node else {
+            accepts.push(StateSet::new());
+            continue;
+        };
+        let pattern = simplify(node).assemble(&mut context);
+        fragment.union(&pattern);
+        fragment.connect(NFAInput::new("".to_string(), start), pattern.start.unwrap());
+        accepts.push(pattern.accepts.unwrap());
+    }
+    fragment.start = Some(start);
+    fragment.accepts = Some(accepts.iter().flatten().copied().collect());
+    (fragment.build(), accepts)
+}
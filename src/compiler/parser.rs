@@ -1,103 +1,160 @@
 use crate::automaton::nfa::NFA;
+use crate::syntax::{Ast, AstKind, Error, Span};
 
 use super::{
-    ast::{Interpreter, NewNode, NodeKind},
+    ast::{lower, NewNode},
+    compile_lowered,
     token::{Token, TokenKind},
-    Context,
 };
 
 /// parse a list of tokens into an AST
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    /// names of the named groups seen so far
+    names: Vec<String>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            names: vec![],
+        }
     }
 
     pub fn parse(&mut self) -> NFA {
         self.expr()
     }
 
+    /// parse into the public syntax tree
+    pub fn parse_ast(&mut self) -> Ast {
+        self.try_parse_ast()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// like [`Parser::parse_ast`], returning the first error instead of panicking
+    pub fn try_parse_ast(&mut self) -> Result<Ast, Error> {
+        let ast = self.sub_expr()?;
+        self.expect(TokenKind::Eof)?;
+        Ok(ast)
+    }
+
+    /// parse into an AST without assembling an NFA, `None` if the pattern matches nothing
+    pub fn parse_node(&mut self) -> Option<NewNode> {
+        lower(&self.parse_ast())
+    }
+
     /// expr = sub_expr EOF
     pub fn expr(&mut self) -> NFA {
-        let node = self.sub_expr();
-        self.expect(TokenKind::Eof);
-
-        let mut context = Context::default();
-        let fragment = node.assemble(&mut context);
-        let nfa = fragment.build();
-        nfa
+        compile_lowered(self.parse_node())
     }
 
     /// sub_expr = (seq '|' sub_expr) | seq
-    pub fn sub_expr(&mut self) -> NewNode {
-        let node = self.seq();
+    pub fn sub_expr(&mut self) -> Result<Ast, Error> {
+        let ast = self.seq()?;
 
         if self.peek().kind == TokenKind::Union {
-            self.expect(TokenKind::Union);
-            let right = self.sub_expr();
-            let new = self.new_union(Some(node), Some(right));
-            new
+            self.expect(TokenKind::Union)?;
+            let right = self.sub_expr()?;
+            Ok(self.new_union(ast, right))
         } else {
-            node
+            Ok(ast)
         }
     }
 
     /// sequence = sub_sequence | ""
-    pub fn seq(&mut self) -> NewNode {
+    pub fn seq(&mut self) -> Result<Ast, Error> {
         match self.peek().kind {
-            TokenKind::LParen | TokenKind::Char => {
-                let node = self.sub_seq();
-                node
+            TokenKind::LParen
+            | TokenKind::LParenNonCapturing
+            | TokenKind::LParenNamed
+            | TokenKind::Never
+            | TokenKind::Char => self.sub_seq(),
+            // an empty alternative, as in `a|`, `(|a)` or `()`
+            TokenKind::Union | TokenKind::RParen | TokenKind::Eof => {
+                let start = self.peek().span.start;
+                Ok(Ast::new(AstKind::Empty, Span::new(start, start)))
             }
-            // FIXME: RPAREN が来るケースもあるようだが具体例を思いつかないのでそのテストケースを作った時に追加する
-            TokenKind::Union | TokenKind::Eof => self.new_char("".to_string()),
-            _ => panic!("unexpected token: {:?}", self.peek()),
+            _ => Err(self.unexpected()),
         }
     }
 
     /// sub_sequence = star sub_sequence | star
-    pub fn sub_seq(&mut self) -> NewNode {
-        let node = self.star();
+    pub fn sub_seq(&mut self) -> Result<Ast, Error> {
+        let ast = self.star()?;
 
         match self.peek().kind {
-            TokenKind::LParen | TokenKind::Char => {
-                let right = self.sub_seq();
-                self.new_concat(Some(node), Some(right))
+            TokenKind::LParen
+            | TokenKind::LParenNonCapturing
+            | TokenKind::LParenNamed
+            | TokenKind::Never
+            | TokenKind::Char => {
+                let right = self.sub_seq()?;
+                Ok(self.new_concat(ast, right))
             }
-            TokenKind::Union | TokenKind::RParen | TokenKind::Eof => {
-                return node;
-            }
-            _ => panic!("unexpected token: {:?}", self.peek()),
+            TokenKind::Union | TokenKind::RParen | TokenKind::Eof => Ok(ast),
+            _ => Err(self.unexpected()),
         }
     }
 
     /// star = primary | primary"*"
-    pub fn star(&mut self) -> NewNode {
-        let mut node = self.primary();
+    pub fn star(&mut self) -> Result<Ast, Error> {
+        let mut ast = self.primary()?;
         if self.peek().kind == TokenKind::Star {
-            self.expect(TokenKind::Star);
-            node = self.new_star(Some(node));
+            let end = self.peek().span.end;
+            self.expect(TokenKind::Star)?;
+            let span = Span::new(ast.span.start, end);
+            ast = Ast::new(AstKind::Star(Box::new(ast)), span);
         }
-        node
+        Ok(ast)
     }
 
-    /// primary = "(" sub_expr ")" | CHAR
-    pub fn primary(&mut self) -> NewNode {
-        let token = self.next_token();
+    /// primary = "(" sub_expr ")" | "(?:" sub_expr ")" | "(?P<" NAME ">" sub_expr ")" | "(?!)"
+    ///         | CHAR
+    pub fn primary(&mut self) -> Result<Ast, Error> {
+        if !matches!(
+            self.peek().kind,
+            TokenKind::Char
+                | TokenKind::LParen
+                | TokenKind::LParenNonCapturing
+                | TokenKind::LParenNamed
+                | TokenKind::Never
+        ) {
+            return Err(self.unexpected());
+        }
+        let token = self.next_token().clone();
         let ch = token.val.clone();
+        let start = token.span.start;
+        if token.kind == TokenKind::LParenNamed {
+            let name = ch.clone().unwrap();
+            if self.names.contains(&name) {
+                let message = format!("duplicate group name: {}", name);
+                return Err(Error::new(message, token.span));
+            }
+            self.names.push(name);
+        }
 
         match token.kind {
-            super::token::TokenKind::Char => self.new_char(ch.unwrap()),
-            super::token::TokenKind::LParen => {
-                let node = self.sub_expr();
-                self.expect(TokenKind::RParen);
-                node
+            TokenKind::Char => {
+                let ch = ch.unwrap().chars().next().unwrap();
+                Ok(Ast::new(AstKind::Literal(ch), token.span))
+            }
+            // a union without alternatives
+            TokenKind::Never => Ok(Ast::new(AstKind::Union(vec![]), token.span)),
+            _ => {
+                let kind = token.kind;
+                let ast = Box::new(self.sub_expr()?);
+                let end = self.peek().span.end;
+                self.expect(TokenKind::RParen)?;
+                let kind = match kind {
+                    TokenKind::LParen => AstKind::Group(ast),
+                    TokenKind::LParenNamed => AstKind::NamedGroup(ch.unwrap(), ast),
+                    _ => AstKind::NonCapturing(ast),
+                };
+                Ok(Ast::new(kind, Span::new(start, end)))
             }
-            _ => unreachable!(),
         }
     }
 
@@ -116,31 +173,42 @@ impl Parser {
             .unwrap_or_else(|| panic!("unexpected index: {}/{}", self.current, self.tokens.len()))
     }
 
-    pub fn expect(&mut self, kind: TokenKind) {
-        if self.tokens[self.current].kind == kind {
+    pub fn expect(&mut self, kind: TokenKind) -> Result<(), Error> {
+        let token = &self.tokens[self.current];
+        if token.kind == kind {
             self.current += 1;
-            return;
+            Ok(())
         } else {
-            panic!(
-                "expected {:?}, got {:?}",
-                kind, self.tokens[self.current].kind
-            );
+            let message = format!("expected {:?}, got {:?}", kind, token.kind);
+            Err(Error::new(message, token.span))
         }
     }
 
-    pub fn new_union(&mut self, left: Option<NewNode>, right: Option<NewNode>) -> NewNode {
-        NewNode::new(NodeKind::Union, None, left, right)
+    /// the next token, which is not allowed where it is
+    fn unexpected(&mut self) -> Error {
+        let token = self.peek();
+        Error::new(format!("unexpected token: {:?}", token), token.span)
     }
 
-    pub fn new_concat(&mut self, left: Option<NewNode>, right: Option<NewNode>) -> NewNode {
-        NewNode::new(NodeKind::Concat, None, left, right)
-    }
-
-    pub fn new_star(&mut self, origin: Option<NewNode>) -> NewNode {
-        NewNode::new(NodeKind::Star, None, origin, None)
+    /// `left|right`, merged into the alternatives of `right` if it is a union itself
+    pub fn new_union(&mut self, left: Ast, right: Ast) -> Ast {
+        let span = Span::new(left.span.start, right.span.end);
+        let mut asts = vec![left];
+        match right.kind {
+            AstKind::Union(rest) => asts.extend(rest),
+            _ => asts.push(right),
+        }
+        Ast::new(AstKind::Union(asts), span)
     }
 
-    pub fn new_char(&mut self, ch: String) -> NewNode {
-        NewNode::new(NodeKind::Char, Some(ch), None, None)
+    /// `left right`, merged into the items of `right` if it is a concatenation itself
+    pub fn new_concat(&mut self, left: Ast, right: Ast) -> Ast {
+        let span = Span::new(left.span.start, right.span.end);
+        let mut asts = vec![left];
+        match right.kind {
+            AstKind::Concat(rest) => asts.extend(rest),
+            _ => asts.push(right),
+        }
+        Ast::new(AstKind::Concat(asts), span)
     }
 }
@@ -0,0 +1,155 @@
+use super::ast::{GroupNode, NewNode, NodeKind};
+
+/// Rewrite the AST into an equivalent, smaller one before it is assembled into an NFA.
+///
+/// - nested concatenations and unions are flattened, dropping empty concatenation operands
+/// - duplicate alternatives are removed, keeping the first one
+/// - adjacent alternatives sharing a first element are factored: `abc|abd` becomes `ab(c|d)`
+/// - `(a*)*` collapses to `a*` and `()*` to `()`
+///
+/// Alternatives are never reordered, so the first matching alternative stays the first, and
+/// capturing groups are kept.
+pub fn simplify(node: NewNode) -> NewNode {
+    match node {
+        NewNode::Char(_) => node,
+        NewNode::Concat(_) => {
+            let mut operands = vec![];
+            flatten_concat(node, &mut operands);
+            concat(operands)
+        }
+        NewNode::Union(_) => {
+            let mut alternatives = vec![];
+            flatten_union(node, &mut alternatives);
+            union(alternatives)
+        }
+        NewNode::Star(node) => star(simplify(*node.origin)),
+        // a group must stay to capture, only its contents can change
+        NewNode::Group(node) => NewNode::Group(GroupNode {
+            index: node.index,
+            name: node.name,
+            origin: Box::new(simplify(*node.origin)),
+        }),
+    }
+}
+
+fn flatten_concat(node: NewNode, operands: &mut Vec<NewNode>) {
+    match node {
+        NewNode::Concat(node) => {
+            flatten_concat(*node.left, operands);
+            flatten_concat(*node.right, operands);
+        }
+        node => match simplify(node) {
+            // factoring may have produced a concatenation again
+            node @ NewNode::Concat(_) => flatten_concat(node, operands),
+            node if node.is_empty() => {}
+            node => operands.push(node),
+        },
+    }
+}
+
+fn flatten_union(node: NewNode, alternatives: &mut Vec<Vec<NewNode>>) {
+    match node {
+        NewNode::Union(node) => {
+            flatten_union(*node.left, alternatives);
+            flatten_union(*node.right, alternatives);
+        }
+        node => match simplify(node) {
+            // factoring may have produced a union again
+            node @ NewNode::Union(_) => flatten_union(node, alternatives),
+            node => {
+                let mut operands = vec![];
+                flatten_concat(node, &mut operands);
+                alternatives.push(operands);
+            }
+        },
+    }
+}
+
+/// right-nested concatenation of `operands`, the empty string if there are none
+fn concat(operands: Vec<NewNode>) -> NewNode {
+    operands
+        .into_iter()
+        .rev()
+        .reduce(|right, left| NewNode::new(NodeKind::Concat, None, Some(left), Some(right)))
+        .unwrap_or_else(|| NewNode::new(NodeKind::Char, Some("".to_string()), None, None))
+}
+
+/// right-nested union of `alternatives`, each given as a list of concatenated operands
+fn union(alternatives: Vec<Vec<NewNode>>) -> NewNode {
+    let mut unique: Vec<Vec<NewNode>> = vec![];
+    for alternative in alternatives {
+        if !unique.contains(&alternative) {
+            unique.push(alternative);
+        }
+    }
+
+    // group adjacent alternatives by their first operand
+    let mut nodes = vec![];
+    let mut rest = unique.into_iter().peekable();
+    while let Some(alternative) = rest.next() {
+        let Some(head) = alternative.first().cloned() else {
+            nodes.push(concat(alternative));
+            continue;
+        };
+        let mut tails = vec![alternative[1..].to_vec()];
+        while let Some(next) = rest.next_if(|next| next.first() == Some(&head)) {
+            tails.push(next[1..].to_vec());
+        }
+        if tails.len() == 1 {
+            nodes.push(concat(alternative));
+        } else {
+            let tail = simplify(union_node(tails.into_iter().map(concat).collect()));
+            nodes.push(concat([vec![head], concat_operands(tail)].concat()));
+        }
+    }
+    union_node(nodes)
+}
+
+fn union_node(nodes: Vec<NewNode>) -> NewNode {
+    nodes
+        .into_iter()
+        .rev()
+        .reduce(|right, left| NewNode::new(NodeKind::Union, None, Some(left), Some(right)))
+        .unwrap()
+}
+
+fn concat_operands(node: NewNode) -> Vec<NewNode> {
+    let mut operands = vec![];
+    flatten_concat(node, &mut operands);
+    operands
+}
+
+fn star(origin: NewNode) -> NewNode {
+    match origin {
+        NewNode::Star(_) => origin,
+        origin if origin.is_empty() => origin,
+        origin => NewNode::new(NodeKind::Star, None, Some(origin), None),
+    }
+}
+
+#[cfg(test)]
+mod simplify_tests {
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    fn simplified(pattern: &str) -> String {
+        let tokens = Lexer::new(pattern.to_string()).tokenize();
+        simplify(Parser::new(tokens).parse_node().unwrap()).to_string()
+    }
+
+    #[test]
+    fn simplify_patterns() {
+        assert_eq!(simplified("abc|abd"), "ab(?:c|d)");
+        assert_eq!(simplified("abc|abd|ab|x"), "ab(?:c|d|)|x");
+        assert_eq!(simplified("abc|x|abd"), "abc|x|abd");
+        assert_eq!(simplified("x(?:abc|abd)y"), "xab(?:c|d)y");
+        assert_eq!(simplified("(?:a*)*"), "a*");
+        assert_eq!(simplified("(?:)*b"), "b");
+        assert_eq!(simplified("a|b|a|(?:b|c)"), "a|b|c");
+        assert_eq!(simplified("(?:ab)(?:c(?:d))"), "abcd");
+        assert_eq!(simplified("a(?:)|"), "a|");
+        // groups capture, so they stay even where they are redundant
+        assert_eq!(simplified("((a*)*)|b(c)|b(c)"), "((a*)*)|b(?:(c)|(c))");
+    }
+}
@@ -1,21 +1,35 @@
+use crate::syntax::Span;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenKind {
     Char,
     Union,
     Star,
     LParen,
+    /// `(?:`
+    LParenNonCapturing,
+    /// `(?P<name>` or `(?<name>`, with the name as value
+    LParenNamed,
     RParen,
+    /// `(?!)`, which matches nothing
+    Never,
     Eof,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub val: Option<String>,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(kind: TokenKind, val: Option<String>) -> Self {
-        Self { kind, val }
+    pub fn new(kind: TokenKind, val: Option<String>, span: Span) -> Self {
+        Self { kind, val, span }
     }
 }
+
+/// characters the lexer reads as literals without a `\` escape
+pub fn is_plain_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+}
@@ -1,34 +1,464 @@
-use crate::automaton::runtime::Runtime;
-use automaton::nfa::NFA;
-use compiler::{lexer::Lexer, parser::Parser};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use automaton::{
+    aho_corasick::AhoCorasick,
+    count,
+    dfa::DFA,
+    eliminate, generate,
+    lazy::{LazyCache, LazyDFA},
+    leftmost::SpanSearcher,
+    nfa::NFA,
+    pikevm::PikeVM,
+    StateSet,
+};
+use compiler::{ast::NewNode, lexer::Lexer, parser::Parser};
+use meta::literal::{self, Literals};
+use search::Walker;
+use syntax::Ast;
 use viz::graph_viz::GraphViz;
 
+pub use automaton::count::Growth;
+pub use automaton::generate::{Examples, RandomExamples};
+pub use automaton::runtime::Runtime;
+pub use automaton::State;
+pub use meta::{MatchKind, Output, Strategy};
+pub use replace::Replacer;
+pub use search::{Captures, Match, Matches, OverlappingMatches, PrefixStatus, Split, SplitN};
+pub use set::RegExpSet;
+pub use stream::{ReaderMatches, StreamMatcher};
+pub use syntax::Pattern;
+pub use table::StaticDFA;
+pub use tokenizer::{Token, Tokenizer};
+pub use util::biguint::BigUint;
+
 mod automaton;
+pub mod bytes;
 mod compiler;
+pub mod meta;
+pub mod replace;
+pub mod search;
+pub mod set;
+pub mod stream;
+pub mod syntax;
+pub mod table;
+pub mod tokenizer;
+mod util;
 mod viz;
 
 pub struct RegExp {
     nfa: NFA,
+    /// `nfa` reversed, to find where matches start
+    reverse: NFA,
+    /// built up front if it fits in the size limit, otherwise on first use by an analysis
+    dfa: OnceLock<DFA>,
+    /// states of `dfa` that can still reach an accept state
+    live: OnceLock<StateSet>,
+    /// states of `nfa` that can still reach an accept state, for the lazy DFA's states
+    nfa_live: OnceLock<StateSet>,
+    /// states of anchored lazy DFAs built by earlier searches, one per concurrent search
+    lazy_caches: Mutex<Vec<LazyCache>>,
+    pikevm: PikeVM,
+    /// index of each named group
+    group_names: Arc<HashMap<String, usize>>,
+    literals: Literals,
+    aho_corasick: Option<AhoCorasick>,
+    match_kind: MatchKind,
+    strategy: Option<Strategy>,
 }
 
 impl RegExp {
     pub fn new(regex: String) -> Self {
-        let mut lexer = Lexer::new(regex.clone());
-        let tokens = lexer.tokenize();
+        RegExpBuilder::new().build(&regex)
+    }
+
+    /// Compile a syntax tree, e.g. one rewritten with [`syntax::Fold`]. If two groups have the
+    /// same name, which only a rewritten tree can, [`Captures::name`] refers to the first one.
+    pub fn from_ast(ast: &Ast) -> Self {
+        RegExpBuilder::new().build_ast(ast)
+    }
+
+    /// Compile a pattern made with the [`Pattern`] builder.
+    pub fn from_pattern(pattern: &Pattern) -> Self {
+        RegExpBuilder::new().build_pattern(pattern)
+    }
+
+    /// The engine that a search over `haystack` producing `output` runs with.
+    pub fn strategy(&self, haystack: &str, output: Output) -> Strategy {
+        let available = [
+            self.literals.exact.as_ref().map(|_| Strategy::Literal),
+            self.aho_corasick.as_ref().map(|_| Strategy::AhoCorasick),
+            self.dfa.get().map(|_| Strategy::Dfa),
+        ];
+        let available: Vec<Strategy> = available.into_iter().flatten().collect();
+        meta::choose(self.strategy, &available, haystack.len(), output)
+    }
+
+    fn dfa(&self) -> &DFA {
+        self.dfa.get_or_init(|| self.nfa.nfa2dfa())
+    }
+
+    /// an anchored lazy DFA that keeps the states built by earlier calls, to give back with
+    /// `put_lazy_dfa`
+    fn take_lazy_dfa(&self) -> LazyDFA<'_> {
+        let cache = self.lazy_caches.lock().unwrap().pop();
+        match cache {
+            Some(cache) => LazyDFA::with_cache(&self.nfa, cache),
+            None => LazyDFA::new(&self.nfa),
+        }
+    }
 
-        let mut parser = Parser::new(tokens);
-        let nfa = parser.parse();
+    fn put_lazy_dfa(&self, dfa: LazyDFA<'_>) {
+        self.lazy_caches.lock().unwrap().push(dfa.into_cache());
+    }
+
+    /// run `f` on an anchored lazy DFA that keeps the states built by earlier calls
+    fn with_lazy_dfa<T>(&self, f: impl FnOnce(&mut LazyDFA<'_>) -> T) -> T {
+        let mut dfa = self.take_lazy_dfa();
+        let result = f(&mut dfa);
+        self.put_lazy_dfa(dfa);
+        result
+    }
 
-        // TODO: ここで DFA に変換したいが、関数内で作成した NFA を DFA の transition (closure) で使用しており、
-        // その関数が NFA の lifetime を持っているため、NFA が drop されてしまう
+    /// run `f` on the DFA if it was built, or else on an anchored lazy DFA
+    fn with_walker<T>(&self, f: impl FnOnce(&mut Walker<'_>) -> T) -> T {
+        let mut walker = match self.dfa.get() {
+            Some(dfa) => Walker::Dfa(Runtime::new(dfa)),
+            None => Walker::Lazy(self.take_lazy_dfa(), 0),
+        };
+        let result = f(&mut walker);
+        if let Walker::Lazy(dfa, _) = walker {
+            self.put_lazy_dfa(dfa);
+        }
+        result
+    }
 
-        Self { nfa }
+    fn live_states(&self) -> &StateSet {
+        self.live.get_or_init(|| self.dfa().live_states())
+    }
+
+    /// whether some continuation leads `state` of a lazy DFA to a match
+    fn is_lazy_live(&self, dfa: &LazyDFA<'_>, state: usize) -> bool {
+        let live = self.nfa_live.get_or_init(|| self.nfa.live_states());
+        !dfa.subset(state).is_disjoint(live)
+    }
+
+    /// the DFA state after `input`, or `None` once it is dead
+    fn walk(&self, input: &str) -> Option<State> {
+        let dfa = self.dfa();
+        input
+            .chars()
+            .try_fold(dfa.start, |state, ch| dfa.next(state, ch))
+    }
+
+    /// Parse `regex` into its syntax tree without compiling it.
+    pub fn parse_ast(regex: &str) -> Ast {
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        Parser::new(tokens).parse_ast()
+    }
+
+    /// Like [`RegExp::parse_ast`], returning an error with the span at fault instead of
+    /// panicking on an invalid pattern.
+    pub fn try_parse_ast(regex: &str) -> Result<Ast, syntax::Error> {
+        let tokens = Lexer::new(regex.to_string()).try_tokenize()?;
+        Parser::new(tokens).try_parse_ast()
     }
 
     pub fn matches(&self, input: String) -> bool {
-        let dfa = self.nfa.nfa2dfa();
-        let mut runtime = Runtime::new(&dfa);
-        runtime.run(input)
+        if !self.literals.may_match(&input) {
+            return false;
+        }
+        match self.strategy(&input, Output::Bool) {
+            Strategy::Dfa => Runtime::new(self.dfa()).run(input),
+            Strategy::LazyDfa => self.with_lazy_dfa(|dfa| dfa.is_match(&input)),
+            Strategy::PikeVm => self.pikevm.is_full_match(&input),
+            // `may_match` compared `input` with the literal already
+            Strategy::Literal => true,
+            Strategy::AhoCorasick => self.aho_corasick.as_ref().unwrap().is_full_match(&input),
+        }
+    }
+
+    /// The leftmost match anywhere in `haystack`: the match that starts earliest, and among
+    /// those the one picked by the [`MatchKind`], by default the one preferred by the pattern
+    /// (the left side of `|`, and as many repetitions of `*` as possible).
+    pub fn find<'h>(&self, haystack: &'h str) -> Option<Match<'h>> {
+        self.find_at(haystack, 0, &mut None)
+    }
+
+    /// Every non-overlapping match, from left to right. An empty match right where the previous
+    /// match ends is skipped.
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h str) -> Matches<'r, 'h> {
+        Matches::new(self, haystack)
+    }
+
+    /// Like [`RegExp::find`], for matches starting at byte offset `at` or later. `searcher`
+    /// keeps the lazy DFAs' states between calls.
+    pub(crate) fn find_at<'r, 'h>(
+        &'r self,
+        haystack: &'h str,
+        at: usize,
+        searcher: &mut Option<SpanSearcher<'r>>,
+    ) -> Option<Match<'h>> {
+        let (start, end) = match self.strategy(haystack, Output::Span) {
+            Strategy::Literal => {
+                let literal = self.literals.exact.as_ref().unwrap();
+                let start = at + haystack[at..].find(literal.as_str())?;
+                (start, start + literal.len())
+            }
+            Strategy::AhoCorasick => {
+                let aho_corasick = self.aho_corasick.as_ref().unwrap();
+                let (start, end) = aho_corasick.find(&haystack[at..], self.match_kind)?;
+                (at + start, at + end)
+            }
+            Strategy::LazyDfa => {
+                let at = self.literals.candidate(haystack, at)?;
+                searcher
+                    .get_or_insert_with(|| {
+                        SpanSearcher::new(&self.nfa, &self.reverse, self.match_kind)
+                    })
+                    .find_at(haystack, at)?
+            }
+            _ => {
+                let at = self.literals.candidate(haystack, at)?;
+                let slots = self.pikevm.search(haystack, at, false)?;
+                (slots[0].unwrap(), slots[1].unwrap())
+            }
+        };
+        Some(Match::new(haystack, start, end))
+    }
+
+    /// The pieces of `haystack` around the matches of [`RegExp::find_iter`], as many as there
+    /// are matches plus one. A match at either end gives an empty piece there.
+    pub fn split<'r, 'h>(&'r self, haystack: &'h str) -> Split<'r, 'h> {
+        Split::new(self, haystack)
+    }
+
+    /// Like [`RegExp::split`], stopping after `limit` pieces, the last of which is the rest of
+    /// `haystack`.
+    pub fn splitn<'r, 'h>(&'r self, haystack: &'h str, limit: usize) -> SplitN<'r, 'h> {
+        SplitN::new(self, haystack, limit)
+    }
+
+    /// Like [`RegExp::find`], along with the span of every capturing group.
+    pub fn captures<'h>(&self, haystack: &'h str) -> Option<Captures<'h>> {
+        let at = self.literals.candidate(haystack, 0)?;
+        let slots = self.pikevm.search(haystack, at, false)?;
+        Some(Captures::new(haystack, slots, self.group_names.clone()))
+    }
+
+    /// Replace the leftmost match with `replacer`, either a template like `"$1"` or a closure,
+    /// see [`Replacer`]. `haystack` is borrowed back when nothing matches.
+    pub fn replace<'h, R: Replacer>(&self, haystack: &'h str, replacer: R) -> Cow<'h, str> {
+        self.replacen(haystack, 1, replacer)
+    }
+
+    /// Replace every match of [`RegExp::find_iter`] with `replacer`.
+    pub fn replace_all<'h, R: Replacer>(&self, haystack: &'h str, replacer: R) -> Cow<'h, str> {
+        self.replacen(haystack, 0, replacer)
+    }
+
+    /// Replace the first `limit` matches of [`RegExp::find_iter`] with `replacer`, or all of them
+    /// if `limit` is 0.
+    pub fn replacen<'h, R: Replacer>(
+        &self,
+        haystack: &'h str,
+        limit: usize,
+        mut replacer: R,
+    ) -> Cow<'h, str> {
+        let mut matches = self.find_iter(haystack).peekable();
+        if matches.peek().is_none() {
+            return Cow::Borrowed(haystack);
+        }
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let literal = replacer.no_expansion().map(Cow::into_owned);
+        let needs_groups = replacer.needs_groups();
+        let mut replaced = String::with_capacity(haystack.len());
+        let mut last = 0;
+        for m in matches.take(limit) {
+            replaced.push_str(&haystack[last..m.start()]);
+            match &literal {
+                Some(literal) => replaced.push_str(literal),
+                None => {
+                    let caps = self.captures_of(haystack, m, needs_groups);
+                    replacer.replace_append(&caps, &mut replaced);
+                }
+            }
+            last = m.end();
+        }
+        replaced.push_str(&haystack[last..]);
+        Cow::Owned(replaced)
+    }
+
+    /// the groups of `m`, a match found in `haystack`, or only group 0 unless `groups`
+    fn captures_of<'h>(&self, haystack: &'h str, m: Match<'h>, groups: bool) -> Captures<'h> {
+        let slots = if groups {
+            // the Pike VM finds the match again from its start, along with its groups
+            self.pikevm.search(haystack, m.start(), true).unwrap()
+        } else {
+            let mut slots = vec![None; self.pikevm.slot_len()];
+            slots[0] = Some(m.start());
+            slots[1] = Some(m.end());
+            slots
+        };
+        Captures::new(haystack, slots, self.group_names.clone())
+    }
+
+    /// A runtime to feed the DFA one character at a time.
+    ///
+    /// This materializes the full DFA, even past [`RegExpBuilder::dfa_size_limit`], so it can
+    /// take exponential time and memory in the size of the pattern.
+    pub fn runtime(&self) -> Runtime<'_> {
+        Runtime::new(self.dfa())
+    }
+
+    /// End offset of the longest prefix of `input` that matches.
+    pub fn longest_prefix(&self, input: &str) -> Option<usize> {
+        self.with_walker(|walker| {
+            let mut longest = walker.is_accepting().then_some(0);
+            for (pos, ch) in input.char_indices() {
+                walker.step(ch);
+                if walker.is_dead() {
+                    break;
+                }
+                if walker.is_accepting() {
+                    longest = Some(pos + ch.len_utf8());
+                }
+            }
+            longest
+        })
+    }
+
+    /// End offset of the shortest prefix of `input` that matches.
+    pub fn shortest_prefix(&self, input: &str) -> Option<usize> {
+        self.with_walker(|walker| {
+            if walker.is_accepting() {
+                return Some(0);
+            }
+            for (pos, ch) in input.char_indices() {
+                walker.step(ch);
+                if walker.is_dead() {
+                    break;
+                }
+                if walker.is_accepting() {
+                    return Some(pos + ch.len_utf8());
+                }
+            }
+            None
+        })
+    }
+
+    /// Whether `input` matches as a whole, could still be extended into a match, or neither,
+    /// e.g. to validate input as it is typed.
+    pub fn prefix_status(&self, input: &str) -> PrefixStatus {
+        if self.dfa.get().is_none() {
+            return self.with_lazy_dfa(|dfa| match dfa.walk(input) {
+                state if dfa.is_accept(state) => PrefixStatus::Match,
+                state if self.is_lazy_live(dfa, state) => PrefixStatus::Viable,
+                _ => PrefixStatus::Dead,
+            });
+        }
+        match self.walk(input) {
+            Some(state) if self.dfa().is_accept(state) => PrefixStatus::Match,
+            Some(state) if self.live_states().contains(&state) => PrefixStatus::Viable,
+            _ => PrefixStatus::Dead,
+        }
+    }
+
+    /// The characters that can follow `input` without making it dead, in ascending order.
+    pub fn next_chars(&self, input: &str) -> Vec<char> {
+        if self.dfa.get().is_none() {
+            return self.with_lazy_dfa(|dfa| {
+                let state = dfa.walk(input);
+                if !self.is_lazy_live(dfa, state) {
+                    return vec![];
+                }
+                let alphabet = self.nfa.alphabet();
+                alphabet
+                    .into_iter()
+                    .filter(|&ch| {
+                        let next = dfa.next(state, ch);
+                        self.is_lazy_live(dfa, next)
+                    })
+                    .collect()
+            });
+        }
+        let Some(state) = self.walk(input) else {
+            return vec![];
+        };
+        let live = self.live_states();
+        self.dfa().map[state.id]
+            .iter()
+            .filter(|(_, next)| live.contains(next))
+            .map(|(&ch, _)| ch)
+            .collect()
+    }
+
+    /// Every match in `haystack`, including ones that overlap or contain each other, as for
+    /// motif scanning. Matches are ordered by start and then by end.
+    pub fn find_overlapping_iter<'r, 'h>(
+        &'r self,
+        haystack: &'h str,
+    ) -> OverlappingMatches<'r, 'h> {
+        let walker = match self.dfa.get() {
+            Some(dfa) => Walker::Dfa(Runtime::new(dfa)),
+            None => Walker::Lazy(LazyDFA::new(&self.nfa), 0),
+        };
+        OverlappingMatches::new(walker, &self.literals, haystack)
+    }
+
+    /// A matcher that takes the input chunk by chunk and reports where matches end.
+    pub fn stream(&self) -> StreamMatcher<'_> {
+        StreamMatcher::new(&self.nfa)
+    }
+
+    /// Every offset in the input read from `reader` where a match ends, read a buffer at a time.
+    pub fn stream_reader<R: Read>(&self, reader: R) -> ReaderMatches<'_, R> {
+        ReaderMatches::new(self.stream(), reader)
+    }
+
+    /// Number of groups in the pattern, including group 0 for the whole match.
+    pub fn captures_len(&self) -> usize {
+        self.pikevm.slot_len() / 2
+    }
+
+    /// Enumerate the accepted strings in shortlex order (by length, then lexicographically).
+    /// The iterator is infinite when the language is.
+    pub fn examples(&self) -> Examples<'_> {
+        Examples::new(self.dfa())
+    }
+
+    /// Draw accepted strings of length `len` uniformly at random. The iterator is empty when no
+    /// string of that length is accepted.
+    pub fn random_examples(&self, len: usize, seed: u64) -> RandomExamples<'_> {
+        RandomExamples::new(self.dfa(), len, seed)
+    }
+
+    /// Up to `limit` rejected strings that are a single edit away from an accepted string.
+    pub fn near_misses(&self, limit: usize) -> Vec<String> {
+        generate::near_misses(self.dfa(), limit)
+    }
+
+    /// Number of accepted strings of length `len`.
+    pub fn count_of_length(&self, len: usize) -> BigUint {
+        count::count_of_length(self.dfa(), len)
+    }
+
+    /// Whether only finitely many strings are accepted.
+    pub fn is_finite(&self) -> bool {
+        count::is_finite(self.dfa())
+    }
+
+    /// Size of the language if finite, otherwise how fast it grows with the string length.
+    pub fn growth(&self) -> Growth {
+        count::growth(self.dfa())
+    }
+
+    /// An equivalent pattern read back from the minimized DFA, or `None` if nothing is
+    /// accepted (the syntax has no way to spell the empty language).
+    pub fn simplified(&self) -> Option<String> {
+        eliminate::dfa_to_ast(&self.dfa().minimize()).map(|node| node.to_string())
     }
 
     pub fn render_nfa(&self, filename: &str) {
@@ -36,3 +466,113 @@ impl RegExp {
         viz.render_nfa_graph(&self.nfa, filename);
     }
 }
+
+/// Options for compiling a [`RegExp`].
+#[derive(Debug, Clone)]
+pub struct RegExpBuilder {
+    dfa_size_limit: usize,
+    match_kind: MatchKind,
+    strategy: Option<Strategy>,
+}
+
+impl Default for RegExpBuilder {
+    fn default() -> Self {
+        Self {
+            dfa_size_limit: meta::DEFAULT_DFA_SIZE_LIMIT,
+            match_kind: MatchKind::default(),
+            strategy: None,
+        }
+    }
+}
+
+impl RegExpBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Materialize the DFA up front only if it has at most `limit` states. Larger patterns
+    /// match with the lazy DFA or the Pike VM instead. [`RegExp::runtime`] still builds the
+    /// full DFA.
+    pub fn dfa_size_limit(mut self, limit: usize) -> Self {
+        self.dfa_size_limit = limit;
+        self
+    }
+
+    /// Which match [`RegExp::find`] and [`RegExp::captures`] report when several start at the
+    /// same offset. [`MatchKind::LeftmostLongest`] follows POSIX, for groups too. Whole-input
+    /// matching does not depend on it.
+    pub fn match_kind(mut self, kind: MatchKind) -> Self {
+        self.match_kind = kind;
+        self
+    }
+
+    /// Run every search that `strategy` supports with it, instead of choosing per search.
+    /// Forcing [`Strategy::Dfa`] builds the DFA regardless of the size limit.
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    pub fn build(&self, regex: &str) -> RegExp {
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        self.build_lowered(Parser::new(tokens).parse_node())
+    }
+
+    pub fn build_ast(&self, ast: &Ast) -> RegExp {
+        self.build_lowered(compiler::ast::lower(ast))
+    }
+
+    pub fn build_pattern(&self, pattern: &Pattern) -> RegExp {
+        self.build_lowered(pattern.node.clone())
+    }
+
+    /// `None` for a pattern that matches nothing
+    fn build_lowered(&self, node: Option<NewNode>) -> RegExp {
+        match node {
+            Some(node) => self.build_node(node),
+            None => {
+                let nfa = compiler::compile_lowered(None);
+                self.build_nfa(nfa, Literals::default(), None, HashMap::new())
+            }
+        }
+    }
+
+    fn build_node(&self, node: NewNode) -> RegExp {
+        let group_names = node.group_names();
+        let literals = Literals::new(&node);
+        let aho_corasick = literal::alternation(&node).map(AhoCorasick::new);
+        self.build_nfa(compiler::compile(node), literals, aho_corasick, group_names)
+    }
+
+    fn build_nfa(
+        &self,
+        nfa: NFA,
+        literals: Literals,
+        aho_corasick: Option<AhoCorasick>,
+        group_names: HashMap<String, usize>,
+    ) -> RegExp {
+        let dfa = OnceLock::new();
+        let built = match self.strategy {
+            Some(Strategy::Dfa) => Some(nfa.nfa2dfa()),
+            _ => nfa.nfa2dfa_limited(self.dfa_size_limit),
+        };
+        if let Some(built) = built {
+            let _ = dfa.set(built);
+        }
+        let pikevm = PikeVM::new(&nfa, self.match_kind);
+        RegExp {
+            reverse: nfa.reverse(),
+            nfa,
+            dfa,
+            live: OnceLock::new(),
+            nfa_live: OnceLock::new(),
+            lazy_caches: Mutex::new(vec![]),
+            pikevm,
+            group_names: Arc::new(group_names),
+            literals,
+            aho_corasick,
+            match_kind: self.match_kind,
+            strategy: self.strategy,
+        }
+    }
+}
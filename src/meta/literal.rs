@@ -0,0 +1,207 @@
+use crate::compiler::ast::NewNode;
+
+/// Literals that every match of a pattern must contain, e.g. `foo(bar|baz)*qux` only matches
+/// strings that start with `foo` and end with `qux`. Substring search for them is much faster
+/// than running an engine, so it can rule out inputs or skip to where a match may start.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Literals {
+    /// the only string matched, if there is just one
+    pub exact: Option<String>,
+    /// every match starts with this
+    pub prefix: String,
+    /// every match ends with this
+    pub suffix: String,
+    /// every match contains this, the longest such literal found
+    pub inner: String,
+}
+
+impl Literals {
+    pub fn new(node: &NewNode) -> Self {
+        match node {
+            NewNode::Char(node) => Self::exact(node.ch.clone()),
+            NewNode::Union(node) => {
+                let (left, right) = (Self::new(&node.left), Self::new(&node.right));
+                if left.exact.is_some() && left.exact == right.exact {
+                    return left;
+                }
+                let prefix = common_prefix(&left.prefix, &right.prefix);
+                let suffix = common_suffix(&left.suffix, &right.suffix);
+                let inner = if left.inner == right.inner {
+                    left.inner
+                } else {
+                    longest([&prefix, &suffix])
+                };
+                Self {
+                    exact: None,
+                    prefix,
+                    suffix,
+                    inner,
+                }
+            }
+            NewNode::Concat(node) => {
+                let (left, right) = (Self::new(&node.left), Self::new(&node.right));
+                if let (Some(left), Some(right)) = (&left.exact, &right.exact) {
+                    return Self::exact(format!("{}{}", left, right));
+                }
+                let prefix = match &left.exact {
+                    Some(exact) => format!("{}{}", exact, right.prefix),
+                    None => left.prefix.clone(),
+                };
+                let suffix = match &right.exact {
+                    Some(exact) => format!("{}{}", left.suffix, exact),
+                    None => right.suffix.clone(),
+                };
+                let middle = format!("{}{}", left.suffix, right.prefix);
+                let inner = longest([&left.inner, &right.inner, &middle, &prefix, &suffix]);
+                Self {
+                    exact: None,
+                    prefix,
+                    suffix,
+                    inner,
+                }
+            }
+            NewNode::Star(node) => match Self::new(&node.origin).exact.as_deref() {
+                Some("") => Self::exact(String::new()),
+                _ => Self::default(),
+            },
+            NewNode::Group(node) => Self::new(&node.origin),
+        }
+    }
+
+    fn exact(literal: String) -> Self {
+        Self {
+            prefix: literal.clone(),
+            suffix: literal.clone(),
+            inner: literal.clone(),
+            exact: Some(literal),
+        }
+    }
+
+    /// Whether the whole of `input` can match; `false` rules it out.
+    pub fn may_match(&self, input: &str) -> bool {
+        match &self.exact {
+            Some(exact) => input == exact,
+            None => {
+                input.starts_with(&self.prefix)
+                    && input.ends_with(&self.suffix)
+                    && input.contains(&self.inner)
+            }
+        }
+    }
+
+    /// The first byte offset at or after `at` where a match can start, or `None` if no match
+    /// can start there.
+    pub fn candidate(&self, haystack: &str, at: usize) -> Option<usize> {
+        let rest = &haystack[at..];
+        if !self.prefix.is_empty() {
+            return rest.find(&self.prefix).map(|start| at + start);
+        }
+        if !rest.contains(&self.inner) {
+            return None;
+        }
+        Some(at)
+    }
+}
+
+/// The literals of a pattern that is an alternation of at least two non-empty literals, in order
+/// of preference.
+pub fn alternation(node: &NewNode) -> Option<Vec<String>> {
+    fn collect(node: &NewNode, literals: &mut Vec<String>) -> bool {
+        match node {
+            NewNode::Union(node) => collect(&node.left, literals) && collect(&node.right, literals),
+            NewNode::Group(node) => collect(&node.origin, literals),
+            _ => match Literals::new(node).exact {
+                Some(literal) if !literal.is_empty() => {
+                    literals.push(literal);
+                    true
+                }
+                _ => false,
+            },
+        }
+    }
+    let mut literals = vec![];
+    (collect(node, &mut literals) && literals.len() > 1).then_some(literals)
+}
+
+fn common_prefix(left: &str, right: &str) -> String {
+    left.chars()
+        .zip(right.chars())
+        .take_while(|(left, right)| left == right)
+        .map(|(ch, _)| ch)
+        .collect()
+}
+
+fn common_suffix(left: &str, right: &str) -> String {
+    let mut suffix: Vec<char> = left
+        .chars()
+        .rev()
+        .zip(right.chars().rev())
+        .take_while(|(left, right)| left == right)
+        .map(|(ch, _)| ch)
+        .collect();
+    suffix.reverse();
+    suffix.into_iter().collect()
+}
+
+fn longest<const N: usize>(literals: [&String; N]) -> String {
+    literals
+        .into_iter()
+        .max_by_key(|literal| literal.len())
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod literal_tests {
+    use crate::compiler::{lexer::Lexer, parser::Parser};
+
+    use super::*;
+
+    fn literals(regex: &str) -> Literals {
+        let tokens = Lexer::new(regex.to_string()).tokenize();
+        Literals::new(&Parser::new(tokens).parse_node().unwrap())
+    }
+
+    #[test]
+    fn extract() {
+        let foo = literals("foo(bar|baz)*qux");
+        assert_eq!(foo.exact, None);
+        assert_eq!(foo.prefix, "foo");
+        assert_eq!(foo.suffix, "qux");
+
+        let union = literals("xab|xcb");
+        assert_eq!((union.prefix.as_str(), union.suffix.as_str()), ("x", "b"));
+        assert_eq!(literals("a*(bcd)e*").inner, "bcd");
+        assert_eq!(literals("ab(c)").exact.as_deref(), Some("abc"));
+        assert_eq!(literals("(ab|ab)").exact.as_deref(), Some("ab"));
+        assert_eq!(literals("(a|b)*"), Literals::default());
+    }
+
+    #[test]
+    fn alternations() {
+        let alternation = |regex: &str| {
+            let tokens = Lexer::new(regex.to_string()).tokenize();
+            alternation(&Parser::new(tokens).parse_node().unwrap())
+        };
+        assert_eq!(
+            alternation("ab|(c|de)|f"),
+            Some(vec!["ab".into(), "c".into(), "de".into(), "f".into()])
+        );
+        assert_eq!(alternation("ab"), None);
+        assert_eq!(alternation("ab|c*"), None);
+        assert_eq!(alternation("ab|"), None);
+    }
+
+    #[test]
+    fn prefilter() {
+        let foo = literals("foo(bar|baz)*qux");
+        assert!(foo.may_match("foobarqux"));
+        assert!(!foo.may_match("fooqu"));
+        assert_eq!(foo.candidate("xxfoo", 0), Some(2));
+        assert_eq!(foo.candidate("xxfoo", 3), None);
+
+        let inner = literals("a*bcd");
+        assert_eq!(inner.candidate("aaabc", 0), None);
+        assert_eq!(inner.candidate("aaabcd", 1), Some(1));
+    }
+}
@@ -0,0 +1,236 @@
+//! Choosing an engine for each search.
+//!
+//! Every engine answers whether the whole input matches, but they differ in cost: the full DFA
+//! takes one table lookup per character yet may need exponentially many states, the lazy DFA
+//! builds only the states a search visits (and finds spans with a second one running the reversed
+//! pattern backwards), and the Pike VM simulates the NFA directly, which is the slowest per
+//! character but has no setup cost and is the only one that reports captures.
+//!
+//! Before any of them runs, the literals that every match contains rule out inputs or skip ahead
+//! to where a match may start. A pattern that is itself a literal needs no engine at all, and one
+//! that is an alternation of literals, like `error|warn|fatal`, is searched with Aho–Corasick.
+
+pub(crate) mod literal;
+
+/// An engine that [`crate::RegExp`] can run a search with.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// the DFA materialized up front
+    Dfa,
+    /// a DFA built during the search, state by state; for spans, one finding where the match
+    /// ends and one of the reversed pattern finding where it starts
+    LazyDfa,
+    /// NFA simulation tracking capture groups
+    PikeVm,
+    /// substring search, for patterns matching a single string
+    Literal,
+    /// multi-literal search, for alternations of literals
+    AhoCorasick,
+}
+
+/// Which match to report when several start at the leftmost position.
+///
+/// A DFA on its own finds the longest match, while backtracking engines such as Perl's find the
+/// one the pattern prefers. POSIX (ERE, `awk`, `grep -E`) asks for the longest, and for groups
+/// to take, from the first to the last, the earliest start and then the longest span they can
+/// within that match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MatchKind {
+    /// the one the pattern prefers: the left side of `|`, and more repetitions of `*`
+    #[default]
+    LeftmostFirst,
+    /// the longest one, with groups following POSIX
+    LeftmostLongest,
+}
+
+/// What a search has to report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Output {
+    /// only whether it matched
+    Bool,
+    /// where the match is
+    Span,
+    /// where the match and each of its groups are
+    Captures,
+}
+
+/// DFAs with more states than this are not materialized up front by default.
+pub const DEFAULT_DFA_SIZE_LIMIT: usize = 10_000;
+
+/// Below this many bytes of input, the lazy DFA does not repay the states it has to build.
+const LAZY_DFA_MIN_LEN: usize = 64;
+
+impl Strategy {
+    /// Whether this engine can produce `output`.
+    pub fn supports(self, output: Output) -> bool {
+        match self {
+            Strategy::Dfa => output == Output::Bool,
+            Strategy::LazyDfa => output != Output::Captures,
+            Strategy::PikeVm => true,
+            Strategy::Literal | Strategy::AhoCorasick => output != Output::Captures,
+        }
+    }
+}
+
+/// The engine to run for a search over `haystack_len` bytes that has to produce `output`, out of
+/// the `available` ones besides the lazy DFA and the Pike VM, which always are. `forced` is used
+/// whenever it can be.
+pub(crate) fn choose(
+    forced: Option<Strategy>,
+    available: &[Strategy],
+    haystack_len: usize,
+    output: Output,
+) -> Strategy {
+    let usable = |strategy: Strategy| {
+        strategy.supports(output)
+            && (matches!(strategy, Strategy::LazyDfa | Strategy::PikeVm)
+                || available.contains(&strategy))
+    };
+    if let Some(strategy) = forced.filter(|&strategy| usable(strategy)) {
+        return strategy;
+    }
+    let lazy = (haystack_len >= LAZY_DFA_MIN_LEN).then_some(Strategy::LazyDfa);
+    [Strategy::Literal, Strategy::AhoCorasick, Strategy::Dfa]
+        .into_iter()
+        .chain(lazy)
+        .find(|&strategy| usable(strategy))
+        .unwrap_or(Strategy::PikeVm)
+}
+
+#[cfg(test)]
+mod meta_tests {
+    use crate::{RegExp, RegExpBuilder};
+
+    use super::*;
+
+    #[test]
+    fn choose_by_output_and_length() {
+        assert_eq!(
+            choose(None, &[Strategy::Dfa], 0, Output::Bool),
+            Strategy::Dfa
+        );
+        assert_eq!(choose(None, &[], 10, Output::Bool), Strategy::PikeVm);
+        assert_eq!(choose(None, &[], 1000, Output::Bool), Strategy::LazyDfa);
+        assert_eq!(
+            choose(None, &[Strategy::Dfa], 10, Output::Span),
+            Strategy::PikeVm
+        );
+        assert_eq!(
+            choose(None, &[Strategy::Dfa], 1000, Output::Span),
+            Strategy::LazyDfa
+        );
+        assert_eq!(
+            choose(Some(Strategy::LazyDfa), &[Strategy::Dfa], 0, Output::Bool),
+            Strategy::LazyDfa
+        );
+        // the DFAs cannot report captures
+        assert_eq!(
+            choose(Some(Strategy::Dfa), &[Strategy::Dfa], 0, Output::Captures),
+            Strategy::PikeVm
+        );
+        assert_eq!(
+            choose(None, &[Strategy::Dfa, Strategy::Literal], 0, Output::Span),
+            Strategy::Literal
+        );
+        assert_eq!(
+            choose(Some(Strategy::Literal), &[Strategy::Dfa], 0, Output::Bool),
+            Strategy::Dfa
+        );
+    }
+
+    #[test]
+    fn every_engine_agrees() {
+        let long = "ab".repeat(100);
+        let inputs = ["", "a", "ab", "aab", "abab", "abb", long.as_str()];
+        for strategy in [Strategy::Dfa, Strategy::LazyDfa, Strategy::PikeVm] {
+            let regexp = RegExpBuilder::new().strategy(strategy).build("(a|ab)*");
+            assert_eq!(regexp.strategy("", Output::Bool), strategy);
+            for input in inputs {
+                assert_eq!(
+                    regexp.matches(input.to_string()),
+                    !input.contains("bb") && !input.starts_with('b'),
+                    "{strategy:?} on {input:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn size_limit_falls_back() {
+        // the DFA for `(a|b)*a(a|b)(a|b)` needs 8 states
+        let regexp = RegExpBuilder::new()
+            .dfa_size_limit(4)
+            .build("(a|b)*a(a|b)(a|b)");
+        assert_eq!(regexp.strategy("abb", Output::Bool), Strategy::PikeVm);
+        assert_eq!(
+            regexp.strategy(&"a".repeat(100), Output::Bool),
+            Strategy::LazyDfa
+        );
+        assert!(regexp.matches("babb".to_string()));
+        assert!(!regexp.matches("abbb".repeat(20)));
+        assert!(regexp.matches("ab".repeat(40) + "aab"));
+        // both long inputs ran on the same lazy DFA
+        assert_eq!(regexp.lazy_caches.lock().unwrap().len(), 1);
+        assert!(!regexp.is_finite());
+        // analysis needs the DFA, which is then used for matching too
+        assert_eq!(regexp.strategy("abb", Output::Bool), Strategy::Dfa);
+    }
+
+    #[test]
+    fn literal_patterns() {
+        let regexp = RegExp::new("(abc)".to_string());
+        assert_eq!(regexp.strategy("", Output::Span), Strategy::Literal);
+        assert_eq!(regexp.strategy("", Output::Captures), Strategy::PikeVm);
+        assert_eq!(regexp.find("xxabcabc").map(|m| m.range()), Some(2..5));
+        assert!(regexp.matches("abc".to_string()));
+        assert!(!regexp.matches("abcabc".to_string()));
+
+        let regexp = RegExp::new("foo(bar|baz)*qux".to_string());
+        assert_eq!(
+            regexp.find("fooquxfoobazqux").map(|m| m.as_str()),
+            Some("fooqux")
+        );
+        assert_eq!(regexp.find("xxxfoobarbazqux").map(|m| m.start()), Some(3));
+        assert_eq!(regexp.find("foobarqu").map(|m| m.start()), None);
+    }
+
+    #[test]
+    fn literal_alternations() {
+        let regexp = RegExp::new("error|warn|(fatal|panic)".to_string());
+        assert_eq!(regexp.strategy("", Output::Span), Strategy::AhoCorasick);
+        assert_eq!(regexp.strategy("", Output::Bool), Strategy::AhoCorasick);
+        assert_eq!(regexp.strategy("", Output::Captures), Strategy::PikeVm);
+        assert!(regexp.matches("panic".to_string()));
+        assert!(!regexp.matches("panics".to_string()));
+
+        // the same leftmost-first match as the Pike VM
+        for (regex, haystack) in [("a|ab", "xxab"), ("ab|a", "xxab"), ("b|ab", "ab")] {
+            let ac = RegExp::new(regex.to_string());
+            let pikevm = RegExpBuilder::new().strategy(Strategy::PikeVm).build(regex);
+            assert_eq!(
+                ac.find(haystack).map(|m| m.range()),
+                pikevm.find(haystack).map(|m| m.range()),
+                "{regex} on {haystack}"
+            );
+        }
+    }
+
+    #[test]
+    fn leftmost_longest() {
+        let regexp = RegExpBuilder::new()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build("(a|ab)(c|bcd)(d*)");
+        let captures = regexp.captures("xabcd").unwrap();
+        let spans: Vec<_> = captures.iter().map(|m| m.unwrap().range()).collect();
+        assert_eq!(spans, vec![1..5, 1..3, 3..4, 4..5]);
+
+        let first = RegExp::new("a|ab".to_string());
+        let longest = RegExpBuilder::new()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build("a|ab");
+        assert_eq!(longest.strategy("", Output::Span), Strategy::AhoCorasick);
+        assert_eq!(first.find("ab").map(|m| m.range()), Some(0..1));
+        assert_eq!(longest.find("ab").map(|m| m.range()), Some(0..2));
+    }
+}
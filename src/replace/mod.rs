@@ -0,0 +1,236 @@
+//! Replacing matches, see [`crate::RegExp::replace`].
+
+use std::borrow::Cow;
+
+use crate::search::Captures;
+
+/// What a match is replaced with: a template string, or a closure computing the replacement from
+/// the match's groups.
+///
+/// In a template, `$1` or `${1}` stands for the text of group 1, `$name` or `${name}` for the
+/// text of the group named `name`, and `$$` for a literal `$`. The longest run of letters, digits
+/// and `_` after `$` is taken as the name, so `${1}a` is needed where `$1a` would name a group
+/// `1a`. A group that does not exist or took no part in the match stands for the empty string, and
+/// a `$` not followed by a name is kept as is.
+pub trait Replacer {
+    /// Append the replacement for the match `caps` to `dst`.
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String);
+
+    /// The replacement for every match, if it does not depend on the match, as for a template
+    /// without `$`. Matches are then replaced without looking at their groups.
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        None
+    }
+
+    /// Whether the replacement depends on groups other than group 0. If not, `caps` only has the
+    /// whole match, which saves finding the groups of every match.
+    fn needs_groups(&self) -> bool {
+        true
+    }
+}
+
+impl Replacer for &str {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        expand(self, caps, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion(self)
+    }
+
+    fn needs_groups(&self) -> bool {
+        needs_groups(self)
+    }
+}
+
+impl Replacer for &String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        expand(self, caps, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion(self)
+    }
+
+    fn needs_groups(&self) -> bool {
+        needs_groups(self)
+    }
+}
+
+impl Replacer for String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        expand(self, caps, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion(self)
+    }
+
+    fn needs_groups(&self) -> bool {
+        needs_groups(self)
+    }
+}
+
+impl<F> Replacer for F
+where
+    F: FnMut(&Captures<'_>) -> String,
+{
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str(&self(caps));
+    }
+}
+
+/// append `template` to `dst` with its references to groups of `caps` replaced
+fn expand(template: &str, caps: &Captures<'_>, dst: &mut String) {
+    for piece in Pieces(template) {
+        match piece {
+            Piece::Text(text) => dst.push_str(text),
+            Piece::Group(name) => {
+                let group = match name.parse::<usize>() {
+                    Ok(index) => caps.get(index),
+                    Err(_) => caps.name(name),
+                };
+                if let Some(group) = group {
+                    dst.push_str(group.as_str());
+                }
+            }
+        }
+    }
+}
+
+/// `template` itself if it has no references to groups
+fn no_expansion(template: &str) -> Option<Cow<'_, str>> {
+    (!template.contains('$')).then_some(Cow::Borrowed(template))
+}
+
+/// whether `template` refers to a group other than group 0
+fn needs_groups(template: &str) -> bool {
+    Pieces(template).any(|piece| matches!(piece, Piece::Group(name) if name.parse() != Ok(0)))
+}
+
+/// a part of a template
+enum Piece<'t> {
+    Text(&'t str),
+    /// a reference to the group with this name or number
+    Group(&'t str),
+}
+
+/// the parts of a template, in order
+struct Pieces<'t>(&'t str);
+
+impl<'t> Iterator for Pieces<'t> {
+    type Item = Piece<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.0;
+        match rest.find('$') {
+            _ if rest.is_empty() => return None,
+            Some(0) => {}
+            Some(dollar) => {
+                self.0 = &rest[dollar..];
+                return Some(Piece::Text(&rest[..dollar]));
+            }
+            None => {
+                self.0 = "";
+                return Some(Piece::Text(rest));
+            }
+        }
+        let rest = &rest[1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            self.0 = after;
+            return Some(Piece::Text("$"));
+        }
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(close) if is_name(&braced[..close]) => {
+                    (&braced[..close], &braced[close + 1..])
+                }
+                _ => ("", rest),
+            },
+            None => {
+                let len = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+                (&rest[..len], &rest[len..])
+            }
+        };
+        if name.is_empty() {
+            self.0 = rest;
+            return Some(Piece::Text("$"));
+        }
+        self.0 = after;
+        Some(Piece::Group(name))
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_name_char)
+}
+
+#[cfg(test)]
+mod replace_tests {
+    use std::borrow::Cow;
+
+    use crate::{Captures, RegExp, Replacer};
+
+    #[test]
+    fn templates() {
+        let regexp = RegExp::new("(?P<user>(a|b)(a|b)*)\\@(x|y)".to_string());
+        let log = "from ab@x to b@y";
+        assert_eq!(regexp.replace_all(log, "<$user>"), "from <ab> to <b>");
+        assert_eq!(regexp.replace_all(log, "${user}_$4"), "from ab_x to b_y");
+        assert_eq!(regexp.replace_all(log, "$5$$$user_"), "from $ to $");
+        assert_eq!(
+            regexp.replace_all(log, "${1}1 $ ${"),
+            "from ab1 $ ${ to b1 $ ${"
+        );
+        assert_eq!(regexp.replace(log, "?"), "from ? to b@y");
+        assert_eq!(regexp.replacen(log, 2, String::from("?")), "from ? to ?");
+    }
+
+    #[test]
+    fn closure() {
+        let regexp = RegExp::new("(a|b)(a|b)*".to_string());
+        let redact = |caps: &Captures<'_>| "*".repeat(caps.get(0).unwrap().as_str().len());
+        assert_eq!(regexp.replace_all("xabyb", redact), "x**y*");
+    }
+
+    #[test]
+    fn groups_only_when_needed() {
+        assert_eq!("a$b".no_expansion(), None);
+        assert_eq!("ab".no_expansion(), Some(Cow::Borrowed("ab")));
+        assert_eq!(String::from("a$$").no_expansion(), None);
+        assert!(!"<$0 ${0} $$1 $ ${1>".needs_groups());
+        assert!("$x".needs_groups() && "${1}".needs_groups());
+
+        /// brackets the whole match, and shows group 1 if it has it
+        struct Whole;
+        impl Replacer for Whole {
+            fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+                let group = caps.get(1).map_or("-", |m| m.as_str());
+                dst.push_str(&format!("[{}{}]", caps.get(0).unwrap().as_str(), group));
+            }
+
+            fn needs_groups(&self) -> bool {
+                false
+            }
+        }
+        let regexp = RegExp::new("(a|b)(a|b)*".to_string());
+        assert_eq!(regexp.replace_all("xaby", Whole), "x[ab-]y");
+        assert_eq!(regexp.replace_all("xaby", "<$0>"), "x<ab>y");
+        assert_eq!(regexp.replace_all("xaby", "<$1>"), "x<a>y");
+    }
+
+    #[test]
+    fn empty_matches_and_no_match() {
+        let regexp = RegExp::new("b*".to_string());
+        assert_eq!(regexp.replace_all("abba", "-"), "-a-a-");
+        let regexp = RegExp::new("z".to_string());
+        assert!(matches!(
+            regexp.replace_all("abc", "-"),
+            Cow::Borrowed("abc")
+        ));
+    }
+}
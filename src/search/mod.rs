@@ -0,0 +1,475 @@
+//! Results of searching a haystack.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::automaton::{lazy::LazyDFA, leftmost::SpanSearcher, runtime::Runtime};
+use crate::meta::literal::Literals;
+use crate::RegExp;
+
+/// A match of a whole pattern or of one of its groups, as a byte range of the haystack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+}
+
+impl<'h> Match<'h> {
+    pub(crate) fn new(haystack: &'h str, start: usize, end: usize) -> Self {
+        Self {
+            haystack,
+            start,
+            end,
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.range()]
+    }
+}
+
+/// The groups of a match. Group 0 is the whole match, and group `i` the `i`-th capturing group
+/// in the order of its opening parenthesis. A group that took no part in the match is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures<'h> {
+    haystack: &'h str,
+    /// `slots[2 * i]` and `slots[2 * i + 1]` are where group `i` starts and ends
+    slots: Vec<Option<usize>>,
+    /// index of each named group
+    names: Arc<HashMap<String, usize>>,
+}
+
+impl<'h> Captures<'h> {
+    pub(crate) fn new(
+        haystack: &'h str,
+        slots: Vec<Option<usize>>,
+        names: Arc<HashMap<String, usize>>,
+    ) -> Self {
+        Self {
+            haystack,
+            slots,
+            names,
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Option<Match<'h>> {
+        let start = (*self.slots.get(i * 2)?)?;
+        let end = (*self.slots.get(i * 2 + 1)?)?;
+        Some(Match::new(self.haystack, start, end))
+    }
+
+    /// The group named `name` with `(?P<name>...)`.
+    pub fn name(&self, name: &str) -> Option<Match<'h>> {
+        self.get(*self.names.get(name)?)
+    }
+
+    /// number of groups, including group 0
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'h>>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+}
+
+/// Iterator over the non-overlapping matches in a haystack, see [`RegExp::find_iter`].
+pub struct Matches<'r, 'h> {
+    regexp: &'r RegExp,
+    haystack: &'h str,
+    /// where the next search starts, `None` when done
+    pos: Option<usize>,
+    last_end: Option<usize>,
+    searcher: Option<SpanSearcher<'r>>,
+}
+
+impl<'r, 'h> Matches<'r, 'h> {
+    pub(crate) fn new(regexp: &'r RegExp, haystack: &'h str) -> Self {
+        Self {
+            regexp,
+            haystack,
+            pos: Some(0),
+            last_end: None,
+            searcher: None,
+        }
+    }
+
+    /// the offset after the character at `pos`, or `None` at the end
+    fn after(&self, pos: usize) -> Option<usize> {
+        let ch = self.haystack[pos..].chars().next()?;
+        Some(pos + ch.len_utf8())
+    }
+}
+
+impl<'h> Iterator for Matches<'_, 'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let found = self
+                .regexp
+                .find_at(self.haystack, self.pos?, &mut self.searcher);
+            let Some(found) = found else {
+                self.pos = None;
+                return None;
+            };
+            if found.is_empty() {
+                self.pos = self.after(found.end);
+                if self.last_end == Some(found.end) {
+                    continue;
+                }
+            } else {
+                self.pos = Some(found.end);
+            }
+            self.last_end = Some(found.end);
+            return Some(found);
+        }
+    }
+}
+
+/// Iterator over the pieces of a haystack between matches, see [`RegExp::split`].
+pub struct Split<'r, 'h> {
+    matches: Matches<'r, 'h>,
+    /// start of the next piece, `None` when done
+    last: Option<usize>,
+}
+
+impl<'r, 'h> Split<'r, 'h> {
+    pub(crate) fn new(regexp: &'r RegExp, haystack: &'h str) -> Self {
+        Self {
+            matches: Matches::new(regexp, haystack),
+            last: Some(0),
+        }
+    }
+}
+
+impl<'h> Iterator for Split<'_, 'h> {
+    type Item = &'h str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let haystack = self.matches.haystack;
+        let last = self.last?;
+        match self.matches.next() {
+            Some(m) => {
+                self.last = Some(m.end());
+                Some(&haystack[last..m.start()])
+            }
+            None => {
+                self.last = None;
+                Some(&haystack[last..])
+            }
+        }
+    }
+}
+
+/// Iterator over at most `limit` pieces of a haystack between matches, the last of which is the
+/// rest of the haystack. See [`RegExp::splitn`].
+pub struct SplitN<'r, 'h> {
+    split: Split<'r, 'h>,
+    limit: usize,
+}
+
+impl<'r, 'h> SplitN<'r, 'h> {
+    pub(crate) fn new(regexp: &'r RegExp, haystack: &'h str, limit: usize) -> Self {
+        Self {
+            split: Split::new(regexp, haystack),
+            limit,
+        }
+    }
+}
+
+impl<'h> Iterator for SplitN<'_, 'h> {
+    type Item = &'h str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.limit {
+            0 => None,
+            1 => {
+                self.limit = 0;
+                let last = self.split.last.take()?;
+                Some(&self.split.matches.haystack[last..])
+            }
+            _ => {
+                self.limit -= 1;
+                self.split.next()
+            }
+        }
+    }
+}
+
+/// Iterator over every match in a haystack, overlapping or not, ordered by start and then by
+/// end. See [`RegExp::find_overlapping_iter`].
+pub struct OverlappingMatches<'r, 'h> {
+    walker: Walker<'r>,
+    literals: &'r Literals,
+    haystack: &'h str,
+    /// start of the matches being reported, `None` when done
+    start: Option<usize>,
+    /// end of the input consumed from `start`
+    pos: usize,
+    fresh: bool,
+}
+
+impl<'r, 'h> OverlappingMatches<'r, 'h> {
+    pub(crate) fn new(walker: Walker<'r>, literals: &'r Literals, haystack: &'h str) -> Self {
+        Self {
+            walker,
+            literals,
+            haystack,
+            start: literals.candidate(haystack, 0),
+            pos: 0,
+            fresh: true,
+        }
+    }
+}
+
+impl<'h> Iterator for OverlappingMatches<'_, 'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.start?;
+            if self.fresh {
+                self.fresh = false;
+                self.pos = start;
+                self.walker.reset();
+                if self.walker.is_accepting() {
+                    return Some(Match::new(self.haystack, start, start));
+                }
+            }
+            match self.haystack[self.pos..].chars().next() {
+                Some(ch) if !self.walker.is_dead() => {
+                    self.walker.step(ch);
+                    self.pos += ch.len_utf8();
+                    if self.walker.is_accepting() {
+                        return Some(Match::new(self.haystack, start, self.pos));
+                    }
+                }
+                _ => {
+                    let next = self.haystack[start..].chars().next();
+                    self.start = next.and_then(|ch| {
+                        self.literals
+                            .candidate(self.haystack, start + ch.len_utf8())
+                    });
+                    self.fresh = true;
+                }
+            }
+        }
+    }
+}
+
+/// An anchored DFA walked one character at a time: the materialized one if it was built, or else
+/// a lazy one with its current state.
+pub(crate) enum Walker<'r> {
+    Dfa(Runtime<'r>),
+    Lazy(LazyDFA<'r>, usize),
+}
+
+impl Walker<'_> {
+    fn reset(&mut self) {
+        match self {
+            Walker::Dfa(runtime) => runtime.reset(),
+            Walker::Lazy(_, state) => *state = 0,
+        }
+    }
+
+    pub(crate) fn step(&mut self, ch: char) {
+        match self {
+            Walker::Dfa(runtime) => runtime.step(ch),
+            Walker::Lazy(dfa, state) => *state = dfa.next(*state, ch),
+        }
+    }
+
+    pub(crate) fn is_accepting(&self) -> bool {
+        match self {
+            Walker::Dfa(runtime) => runtime.is_accepting(),
+            Walker::Lazy(dfa, state) => dfa.is_accept(*state),
+        }
+    }
+
+    pub(crate) fn is_dead(&self) -> bool {
+        match self {
+            Walker::Dfa(runtime) => runtime.is_dead(),
+            Walker::Lazy(dfa, state) => dfa.is_dead(*state),
+        }
+    }
+}
+
+/// How far an input is from being a match, see [`RegExp::prefix_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrefixStatus {
+    /// the input matches as it is
+    Match,
+    /// the input does not match, but some continuation of it does
+    Viable,
+    /// no continuation of the input matches
+    Dead,
+}
+
+#[cfg(test)]
+mod search_tests {
+    use crate::{RegExpBuilder, Strategy};
+
+    use super::*;
+
+    #[test]
+    fn find_and_captures() {
+        let regexp = RegExp::new("(a|b)*(c)".to_string());
+        assert_eq!(regexp.captures_len(), 3);
+        assert_eq!(regexp.find("xxabc").map(|m| m.range()), Some(2..5));
+
+        let captures = regexp.captures("xxabcab").unwrap();
+        assert_eq!(captures.get(0).unwrap().as_str(), "abc");
+        assert_eq!(captures.get(1).unwrap().as_str(), "b");
+        assert_eq!(captures.get(2).unwrap().range(), 4..5);
+
+        let captures = regexp.captures("c").unwrap();
+        assert_eq!(captures.get(1), None);
+        assert!(regexp.captures("ab").is_none());
+    }
+
+    #[test]
+    fn named_groups() {
+        let regexp = RegExp::new("(?P<first>a|b)(c)(?<last>d)*".to_string());
+        let captures = regexp.captures("xbc").unwrap();
+        assert_eq!(captures.name("first").unwrap().as_str(), "b");
+        assert_eq!(captures.name("last"), None);
+        assert_eq!(captures.name("second"), None);
+        assert_eq!(regexp.captures_len(), 4);
+    }
+
+    #[test]
+    fn prefix_status() {
+        let regexp = RegExp::new("ab(c|d)*e".to_string());
+        assert_eq!(regexp.prefix_status(""), PrefixStatus::Viable);
+        assert_eq!(regexp.prefix_status("abcd"), PrefixStatus::Viable);
+        assert_eq!(regexp.prefix_status("abce"), PrefixStatus::Match);
+        assert_eq!(regexp.prefix_status("abcex"), PrefixStatus::Dead);
+        assert_eq!(regexp.prefix_status("b"), PrefixStatus::Dead);
+
+        assert_eq!(regexp.next_chars("ab"), vec!['c', 'd', 'e']);
+        assert_eq!(regexp.next_chars("abe"), vec![]);
+        assert_eq!(regexp.next_chars("x"), vec![]);
+
+        // too large a DFA answers the same on the lazy DFA
+        let regexp = RegExpBuilder::new().dfa_size_limit(1).build("ab(c|d)*e");
+        assert_eq!(regexp.prefix_status("abcd"), PrefixStatus::Viable);
+        assert_eq!(regexp.prefix_status("abce"), PrefixStatus::Match);
+        assert_eq!(regexp.prefix_status("abcex"), PrefixStatus::Dead);
+        assert_eq!(regexp.next_chars("ab"), vec!['c', 'd', 'e']);
+        assert_eq!(regexp.next_chars("abe"), vec![]);
+        assert!(regexp.dfa.get().is_none());
+    }
+
+    #[test]
+    fn prefixes() {
+        let regexp = RegExp::new("a(bc)*".to_string());
+        assert_eq!(regexp.longest_prefix("abcbcbx"), Some(5));
+        assert_eq!(regexp.shortest_prefix("abcbcbx"), Some(1));
+        assert_eq!(regexp.longest_prefix("ba"), None);
+        assert_eq!(regexp.longest_prefix(""), None);
+
+        let regexp = RegExp::new("\\é*".to_string());
+        assert_eq!(regexp.shortest_prefix("ééx"), Some(0));
+        assert_eq!(regexp.longest_prefix("ééx"), Some(4));
+
+        // too large a DFA runs lazily, without building the DFA after all
+        let regexp = RegExpBuilder::new().dfa_size_limit(1).build("a(bc)*");
+        assert_eq!(regexp.longest_prefix("abcbcbx"), Some(5));
+        assert_eq!(regexp.shortest_prefix("abcbcbx"), Some(1));
+        assert_eq!(regexp.longest_prefix("ba"), None);
+        assert!(regexp.dfa.get().is_none());
+    }
+
+    #[test]
+    fn overlapping() {
+        let regexp = RegExp::new("ATA".to_string());
+        let ranges: Vec<_> = regexp
+            .find_overlapping_iter("GATATAT")
+            .map(|m| m.range())
+            .collect();
+        assert_eq!(ranges, vec![1..4, 3..6]);
+
+        let regexp = RegExp::new("T(A|T)*".to_string());
+        let ranges: Vec<_> = regexp
+            .find_overlapping_iter("TAT")
+            .map(|m| m.range())
+            .collect();
+        assert_eq!(ranges, vec![0..1, 0..2, 0..3, 2..3]);
+
+        let regexp = RegExp::new("a*".to_string());
+        assert_eq!(regexp.find_overlapping_iter("ba").count(), 4);
+
+        // too large a DFA runs lazily, without building the DFA after all
+        let regexp = RegExpBuilder::new().dfa_size_limit(1).build("T(A|T)*");
+        let ranges: Vec<_> = regexp
+            .find_overlapping_iter("TAT")
+            .map(|m| m.range())
+            .collect();
+        assert_eq!(ranges, vec![0..1, 0..2, 0..3, 2..3]);
+        assert!(regexp.dfa.get().is_none());
+    }
+
+    #[test]
+    fn find_iter() {
+        let ranges = |regexp: &RegExp, haystack: &str| {
+            regexp
+                .find_iter(haystack)
+                .map(|m| m.range())
+                .collect::<Vec<_>>()
+        };
+        for strategy in [Strategy::LazyDfa, Strategy::PikeVm] {
+            let regexp = RegExpBuilder::new().strategy(strategy).build("a*");
+            // no empty match right after `aaa`
+            assert_eq!(ranges(&regexp, "baaab"), vec![0..0, 1..4, 5..5]);
+            let regexp = RegExpBuilder::new().strategy(strategy).build("ab|c");
+            assert_eq!(ranges(&regexp, "abcxab"), vec![0..2, 2..3, 4..6]);
+            let regexp = RegExpBuilder::new().strategy(strategy).build("\\é");
+            assert_eq!(ranges(&regexp, "éaé"), vec![0..2, 3..5]);
+        }
+        let regexp = RegExp::new("ab|c".to_string());
+        assert_eq!(ranges(&regexp, &"xab".repeat(40)).len(), 40);
+    }
+
+    #[test]
+    fn split() {
+        let split = |regex: &str, haystack| {
+            let regexp = RegExp::new(regex.to_string());
+            regexp.split(haystack).collect::<Vec<_>>().join("/")
+        };
+        assert_eq!(split("\\,\\ *", "a, b,,c"), "a/b//c");
+        assert_eq!(split("\\,", ",a,"), "/a/");
+        assert_eq!(split("\\,", ""), "");
+        assert_eq!(split("b*", "abba"), "/a/a/");
+        assert_eq!(split("", "é"), "/é/");
+
+        let regexp = RegExp::new("\\,".to_string());
+        let splitn = |limit| regexp.splitn("a,b,c", limit).collect::<Vec<_>>();
+        assert_eq!(splitn(0), Vec::<&str>::new());
+        assert_eq!(splitn(1), vec!["a,b,c"]);
+        assert_eq!(splitn(2), vec!["a", "b,c"]);
+        assert_eq!(splitn(5), vec!["a", "b", "c"]);
+    }
+}
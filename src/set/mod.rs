@@ -0,0 +1,178 @@
+//! Matching many patterns at once.
+
+use std::sync::Mutex;
+
+use crate::automaton::{
+    dfa::DFA,
+    lazy::{LazyCache, LazyDFA},
+    nfa::NFA,
+    StateSet,
+};
+use crate::compiler::{self, ast::NewNode};
+use crate::meta::DEFAULT_DFA_SIZE_LIMIT;
+use crate::syntax::Error;
+use crate::RegExp;
+
+/// Several patterns compiled into a single DFA, so that finding which of them match takes one
+/// pass over the input however many patterns there are. Each DFA state remembers the patterns
+/// whose accept states it contains. The DFA is built up front only if it has at most
+/// [`DEFAULT_DFA_SIZE_LIMIT`] states; otherwise its states are built as the input visits them.
+pub struct RegExpSet {
+    nfa: NFA,
+    /// accept states of each pattern
+    accepts: Vec<StateSet>,
+    dfa: Option<DFA>,
+    /// indices of the patterns accepted in each state of `dfa`, in ascending order
+    matched: Vec<Vec<usize>>,
+    /// states of lazy DFAs built by earlier calls when `dfa` is over the size limit
+    lazy_caches: Mutex<Vec<LazyCache>>,
+}
+
+impl RegExpSet {
+    pub fn new<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::try_new(patterns).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Like [`RegExpSet::new`], returning the error of the first pattern that does not parse
+    /// instead of panicking.
+    pub fn try_new<I, S>(patterns: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let nodes = patterns
+            .into_iter()
+            .map(|regex| {
+                Ok(compiler::ast::lower(&RegExp::try_parse_ast(
+                    regex.as_ref(),
+                )?))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self::compile(nodes, DEFAULT_DFA_SIZE_LIMIT))
+    }
+
+    /// `None` for a pattern that matches nothing
+    fn compile(nodes: Vec<Option<NewNode>>, dfa_size_limit: usize) -> Self {
+        let (nfa, accepts) = compiler::compile_set(nodes);
+        let dfa = nfa.nfa2dfa_limited(dfa_size_limit);
+        let matched = dfa
+            .iter()
+            .flat_map(|dfa| &dfa.subsets)
+            .map(|subset| accepted_patterns(&accepts, subset))
+            .collect();
+        Self {
+            nfa,
+            accepts,
+            dfa,
+            matched,
+            lazy_caches: Mutex::new(vec![]),
+        }
+    }
+
+    /// Indices of the patterns that match the whole of `input`, like [`crate::RegExp::matches`],
+    /// in ascending order.
+    pub fn matches(&self, input: &str) -> Vec<usize> {
+        let Some(dfa) = &self.dfa else {
+            return self.lazy_matches(input);
+        };
+        let mut state = dfa.start;
+        for ch in input.chars() {
+            match dfa.next(state, ch) {
+                Some(next) => state = next,
+                None => return vec![],
+            }
+        }
+        self.matched[state.id].clone()
+    }
+
+    /// [`RegExpSet::matches`] on a lazy DFA that keeps the states built by earlier calls
+    fn lazy_matches(&self, input: &str) -> Vec<usize> {
+        let cache = self.lazy_caches.lock().unwrap().pop();
+        let mut dfa = match cache {
+            Some(cache) => LazyDFA::with_cache(&self.nfa, cache),
+            None => LazyDFA::new(&self.nfa),
+        };
+        let mut state = 0;
+        for ch in input.chars() {
+            state = dfa.next(state, ch);
+            if dfa.is_dead(state) {
+                break;
+            }
+        }
+        let result = accepted_patterns(&self.accepts, dfa.subset(state));
+        self.lazy_caches.lock().unwrap().push(dfa.into_cache());
+        result
+    }
+
+    /// Whether any pattern matches the whole of `input`.
+    pub fn is_match(&self, input: &str) -> bool {
+        !self.matches(input).is_empty()
+    }
+
+    /// Number of patterns in the set.
+    pub fn len(&self) -> usize {
+        self.accepts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accepts.is_empty()
+    }
+}
+
+/// indices of the patterns whose accept states, `accepts[i]`, meet `subset`
+fn accepted_patterns(accepts: &[StateSet], subset: &StateSet) -> Vec<usize> {
+    (0..accepts.len())
+        .filter(|&i| !subset.is_disjoint(&accepts[i]))
+        .collect()
+}
+
+#[cfg(test)]
+mod set_tests {
+    use super::*;
+
+    #[test]
+    fn matches_every_pattern_in_one_pass() {
+        let set = RegExpSet::new(["ab*", "a(b|c)", "abb", "x"]);
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.matches("ab"), vec![0, 1]);
+        assert_eq!(set.matches("abb"), vec![0, 2]);
+        assert_eq!(set.matches("ac"), vec![1]);
+        assert_eq!(set.matches("abc"), Vec::<usize>::new());
+        assert!(set.is_match("x"));
+        assert!(!set.is_match("xx"));
+    }
+
+    #[test]
+    fn lazy_over_the_size_limit() {
+        let patterns = ["ab*", "a(b|c)", "abb", "x", "(?!)"];
+        let nodes = patterns
+            .iter()
+            .map(|regex| compiler::ast::lower(&RegExp::parse_ast(regex)))
+            .collect();
+        let lazy = RegExpSet::compile(nodes, 1);
+        assert!(lazy.dfa.is_none());
+        let eager = RegExpSet::new(patterns);
+        for input in ["", "a", "ab", "abb", "ac", "abc", "x", "xx"] {
+            assert_eq!(lazy.matches(input), eager.matches(input), "{}", input);
+        }
+        assert_eq!(lazy.lazy_caches.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let error = RegExpSet::try_new(["a", "(b"]).err().unwrap();
+        assert_eq!(error.message, "expected RParen, got Eof");
+        assert_eq!((error.span.start, error.span.end), (2, 2));
+    }
+
+    #[test]
+    fn empty_set() {
+        let set = RegExpSet::new(Vec::<String>::new());
+        assert!(set.is_empty());
+        assert!(!set.is_match(""));
+    }
+}
@@ -0,0 +1,261 @@
+//! Matching over input that arrives in pieces, like a file read chunk by chunk.
+//!
+//! A stream matcher reports every byte offset where a match ends, counted from the start of the
+//! stream. The input is decoded as UTF-8 across chunk boundaries; a byte that cannot start or
+//! continue a valid sequence is treated as a character that no pattern matches.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+use crate::automaton::{lazy::LazyDFA, nfa::NFA};
+
+/// Feeds input to an unanchored lazy DFA, keeping its state and any incomplete UTF-8 sequence
+/// between chunks. See [`crate::RegExp::stream`].
+pub struct StreamMatcher<'r> {
+    dfa: LazyDFA<'r>,
+    state: usize,
+    offset: usize,
+    /// start of a UTF-8 sequence that the next chunk completes
+    pending: Vec<u8>,
+}
+
+impl<'r> StreamMatcher<'r> {
+    pub(crate) fn new(nfa: &'r NFA) -> Self {
+        Self {
+            dfa: LazyDFA::unanchored(nfa),
+            state: 0,
+            offset: 0,
+            pending: vec![],
+        }
+    }
+
+    /// Consume the next chunk, either `&str` or `&[u8]`, and return the offsets in it where a
+    /// match ends.
+    pub fn feed<T: AsRef<[u8]>>(&mut self, chunk: T) -> Vec<usize> {
+        let mut ends = vec![];
+        self.feed_bytes(chunk.as_ref(), &mut ends);
+        ends
+    }
+
+    /// End the input: an incomplete UTF-8 sequence left at the end is invalid. Returns the
+    /// offsets where a match ends from then on, as [`StreamMatcher::feed`] does.
+    pub fn finish(&mut self) -> Vec<usize> {
+        let mut ends = vec![];
+        if !self.pending.is_empty() {
+            let len = std::mem::take(&mut self.pending).len();
+            self.step(None, len, &mut ends);
+        }
+        ends
+    }
+
+    /// Number of bytes consumed so far, including an incomplete UTF-8 sequence.
+    pub fn offset(&self) -> usize {
+        self.offset + self.pending.len()
+    }
+
+    /// Whether a match ends where the input consumed so far ends, as at the very start for a
+    /// pattern matching the empty string.
+    pub fn is_match(&self) -> bool {
+        self.pending.is_empty() && self.dfa.is_accept(self.state)
+    }
+
+    fn feed_bytes(&mut self, chunk: &[u8], ends: &mut Vec<usize>) {
+        if self.pending.is_empty() {
+            return self.decode(chunk, ends);
+        }
+        // complete the pending sequence first, with as many continuation bytes as it needs
+        let width = utf8_width(self.pending[0]);
+        let take = chunk
+            .iter()
+            .take(width - self.pending.len())
+            .take_while(|&&byte| is_continuation(byte))
+            .count();
+        let mut head = std::mem::take(&mut self.pending);
+        head.extend_from_slice(&chunk[..take]);
+        if head.len() == width {
+            self.decode(&head, ends);
+        } else if take == chunk.len() {
+            self.pending = head;
+            return;
+        } else {
+            // cut short by a byte that cannot continue it
+            self.step(None, head.len(), ends);
+        }
+        self.feed_bytes(&chunk[take..], ends);
+    }
+
+    /// run the characters of `bytes`, keeping an incomplete sequence at the end as pending
+    fn decode(&mut self, mut bytes: &[u8], ends: &mut Vec<usize>) {
+        loop {
+            let (valid, invalid) = match std::str::from_utf8(bytes) {
+                Ok(valid) => (valid, None),
+                Err(error) => {
+                    let valid = std::str::from_utf8(&bytes[..error.valid_up_to()]).unwrap();
+                    (valid, Some(error))
+                }
+            };
+            for ch in valid.chars() {
+                self.step(Some(ch), ch.len_utf8(), ends);
+            }
+            let Some(error) = invalid else { return };
+            let rest = &bytes[valid.len()..];
+            match error.error_len() {
+                Some(len) => {
+                    self.step(None, len, ends);
+                    bytes = &rest[len..];
+                }
+                None => {
+                    self.pending = rest.to_vec();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// advance over `len` bytes holding `ch`, or invalid UTF-8 if `None`
+    fn step(&mut self, ch: Option<char>, len: usize, ends: &mut Vec<usize>) {
+        self.state = match ch {
+            Some(ch) => self.dfa.next(self.state, ch),
+            None => 0,
+        };
+        self.offset += len;
+        if self.dfa.is_accept(self.state) {
+            ends.push(self.offset);
+        }
+    }
+}
+
+/// expected length of the UTF-8 sequence starting with `byte`
+fn utf8_width(byte: u8) -> usize {
+    match byte {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+/// Iterator over the offsets where matches end in a reader, see [`crate::RegExp::stream_reader`].
+pub struct ReaderMatches<'r, R> {
+    matcher: StreamMatcher<'r>,
+    reader: R,
+    buffer: Box<[u8]>,
+    ends: VecDeque<usize>,
+    done: bool,
+}
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+impl<'r, R: Read> ReaderMatches<'r, R> {
+    pub(crate) fn new(matcher: StreamMatcher<'r>, reader: R) -> Self {
+        let ends = matcher.is_match().then_some(0).into_iter().collect();
+        Self {
+            matcher,
+            reader,
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            ends,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for ReaderMatches<'_, R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.ends.is_empty() && !self.done {
+            match self.reader.read(&mut self.buffer) {
+                Ok(0) => {
+                    self.done = true;
+                    self.ends.extend(self.matcher.finish());
+                }
+                Ok(len) => {
+                    let ends = self.matcher.feed(&self.buffer[..len]);
+                    self.ends.extend(ends);
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        self.ends.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod stream_tests {
+    use crate::RegExp;
+
+    #[test]
+    fn matches_across_chunks() {
+        let regexp = RegExp::new("ab|\\é".to_string());
+        let mut matcher = regexp.stream();
+        assert_eq!(matcher.feed("xa"), vec![]);
+        assert_eq!(matcher.feed("bab"), vec![3, 5]);
+        // `é` is C3 A9, split between chunks
+        assert_eq!(matcher.feed(b"\xC3"), vec![]);
+        assert!(!matcher.is_match());
+        assert_eq!(matcher.feed(b"\xA9a\xFFb"), vec![7]);
+        assert_eq!(matcher.offset(), 10);
+    }
+
+    #[test]
+    fn invalid_utf8_matches_nothing() {
+        let regexp = RegExp::new("a\\€".to_string());
+        let mut matcher = regexp.stream();
+        // a truncated `€` (E2 82 AC) followed by a complete one
+        assert_eq!(matcher.feed(b"a\xE2"), vec![]);
+        assert_eq!(matcher.feed(b"\x82a\xE2\x82\xAC"), vec![7]);
+    }
+
+    #[test]
+    fn reader() {
+        let regexp = RegExp::new("b*".to_string());
+        let ends: Vec<usize> = regexp
+            .stream_reader("abb".as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(ends, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn one_byte_per_chunk() {
+        let regexp = RegExp::new("a|\\€".to_string());
+        let input = b"\xE2aa\xE2\x82\xACa\xE2\x82";
+        let mut matcher = regexp.stream();
+        let mut ends = vec![];
+        for byte in input {
+            ends.extend(matcher.feed([*byte]));
+        }
+        // the lone lead byte is invalid as soon as `a` follows it
+        assert_eq!(ends, vec![2, 3, 6, 7]);
+        assert_eq!(matcher.finish(), vec![]);
+        assert_eq!(matcher.offset(), 9);
+
+        /// hands out one byte per read
+        struct Trickle<'a>(&'a [u8]);
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let Some((first, rest)) = self.0.split_first() else {
+                    return Ok(0);
+                };
+                buf[0] = *first;
+                self.0 = rest;
+                Ok(1)
+            }
+        }
+        let regexp = RegExp::new("b*".to_string());
+        let ends: Vec<usize> = regexp
+            .stream_reader(Trickle(b"b\xE2"))
+            .map(Result::unwrap)
+            .collect();
+        // the truncated sequence at the end resets to the start, where `b*` matches
+        assert_eq!(ends, vec![0, 1, 2]);
+    }
+}
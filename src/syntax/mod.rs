@@ -0,0 +1,99 @@
+//! The parsed form of a pattern, for tools that inspect or rewrite patterns.
+
+use std::fmt;
+
+pub mod pattern;
+pub mod visit;
+
+pub use pattern::Pattern;
+pub use visit::{fold_children, Fold, Visitor};
+
+/// Byte range `start..end` of the pattern text that a node was parsed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Why a pattern failed to parse, and the byte range of the pattern text at fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Error {
+    pub fn new(message: String, span: Span) -> Self {
+        Self { message, span }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A node of the parsed pattern together with where it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ast {
+    pub kind: AstKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AstKind {
+    /// The empty string, as in `a|` or `()`.
+    Empty,
+    /// A single character, escaped or not.
+    Literal(char),
+    /// Two or more alternatives: `a|b|c`. A union built without any matches nothing.
+    Union(Vec<Ast>),
+    /// Two or more consecutive items: `abc`. One built without any is the empty string.
+    Concat(Vec<Ast>),
+    /// Zero or more repetitions: `a*`.
+    Star(Box<Ast>),
+    /// A capturing group: `(a)`. Groups are numbered from 1 in the order of their opening
+    /// parentheses.
+    Group(Box<Ast>),
+    /// A parenthesized expression that does not capture: `(?:a)`.
+    NonCapturing(Box<Ast>),
+    /// A capturing group with a name: `(?P<name>a)` or `(?<name>a)`. It is numbered like any
+    /// other group.
+    NamedGroup(String, Box<Ast>),
+}
+
+impl Ast {
+    pub fn new(kind: AstKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// The direct children of this node, in pattern order.
+    pub fn children(&self) -> &[Ast] {
+        match &self.kind {
+            AstKind::Empty | AstKind::Literal(_) => &[],
+            AstKind::Union(asts) | AstKind::Concat(asts) => asts,
+            AstKind::Star(ast)
+            | AstKind::Group(ast)
+            | AstKind::NonCapturing(ast)
+            | AstKind::NamedGroup(_, ast) => std::slice::from_ref(ast),
+        }
+    }
+}
+
+/// Prints the pattern in normalized form: redundant parentheses are dropped and characters are
+/// escaped where needed.
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Pattern::from(self).fmt(f)
+    }
+}
@@ -0,0 +1,199 @@
+use std::fmt;
+
+use crate::compiler::ast::{self, NewNode, NodeKind};
+use crate::syntax::Ast;
+
+/// Builds a pattern from code instead of pattern text, so that values never need escaping.
+///
+/// `Pattern::lit("id-").then(Pattern::any_of("xyz").plus())` accepts the same strings as
+/// `id\-(?:x|y|z)(?:x|y|z)*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    /// `None` for [`Pattern::never`]
+    pub(crate) node: Option<NewNode>,
+}
+
+impl Pattern {
+    /// the empty string
+    pub fn empty() -> Self {
+        Self::char_node("".to_string())
+    }
+
+    /// no string at all, e.g. an alternation of no patterns. It prints as `(?!)`, as in other
+    /// engines, which parses back to it.
+    pub fn never() -> Self {
+        Self { node: None }
+    }
+
+    /// exactly the string `literal`
+    pub fn lit(literal: &str) -> Self {
+        Self::seq(literal.chars().map(|c| Self::char_node(c.to_string())))
+    }
+
+    /// any single character of `chars`, nothing if it is empty
+    pub fn any_of(chars: &str) -> Self {
+        Self::alt(chars.chars().map(|c| Self::char_node(c.to_string())))
+    }
+
+    /// each of `patterns` in turn; the empty string if there are none, and nothing if one of them
+    /// is [`Pattern::never`]
+    pub fn seq(patterns: impl IntoIterator<Item = Pattern>) -> Self {
+        let nodes: Option<Vec<NewNode>> =
+            patterns.into_iter().map(|pattern| pattern.node).collect();
+        match nodes {
+            Some(nodes) => Self::nest(NodeKind::Concat, nodes).unwrap_or_else(Self::empty),
+            None => Self::never(),
+        }
+    }
+
+    /// any one of `patterns`, skipping [`Pattern::never`]; nothing if there are none
+    pub fn alt(patterns: impl IntoIterator<Item = Pattern>) -> Self {
+        let nodes = patterns.into_iter().filter_map(|pattern| pattern.node);
+        Self::nest(NodeKind::Union, nodes).unwrap_or_else(Self::never)
+    }
+
+    /// `self` followed by `next`
+    pub fn then(self, next: Pattern) -> Self {
+        Self::seq([self, next])
+    }
+
+    /// either `self` or `other`
+    pub fn or(self, other: Pattern) -> Self {
+        Self::alt([self, other])
+    }
+
+    /// zero or more repetitions of `self`
+    pub fn star(self) -> Self {
+        match self.node {
+            Some(node) => Self {
+                node: Some(NewNode::new(NodeKind::Star, None, Some(node), None)),
+            },
+            None => Self::empty(),
+        }
+    }
+
+    /// one or more repetitions of `self`
+    pub fn plus(self) -> Self {
+        self.clone().then(self.star())
+    }
+
+    /// `self` or the empty string
+    pub fn optional(self) -> Self {
+        self.or(Self::empty())
+    }
+
+    fn char_node(ch: String) -> Self {
+        Self {
+            node: Some(NewNode::new(NodeKind::Char, Some(ch), None, None)),
+        }
+    }
+
+    /// right-nested like the parser's output, splicing in operands that are of `kind` already
+    fn nest(kind: NodeKind, operands: impl IntoIterator<Item = NewNode>) -> Option<Self> {
+        let mut nodes = vec![];
+        for operand in operands {
+            let mut node = operand;
+            loop {
+                node = match (node, &kind) {
+                    (NewNode::Concat(concat), NodeKind::Concat) => {
+                        nodes.push(*concat.left);
+                        *concat.right
+                    }
+                    (NewNode::Union(union), NodeKind::Union) => {
+                        nodes.push(*union.left);
+                        *union.right
+                    }
+                    (node, _) => {
+                        nodes.push(node);
+                        break;
+                    }
+                };
+            }
+        }
+        nodes
+            .into_iter()
+            .rev()
+            .reduce(|right, left| NewNode::new(kind.clone(), None, Some(left), Some(right)))
+            .map(|node| Self { node: Some(node) })
+    }
+}
+
+/// The pattern of a syntax tree, [`Pattern::never`] if it has a union without alternatives that
+/// it cannot do without.
+impl From<&Ast> for Pattern {
+    fn from(ast: &Ast) -> Self {
+        Self {
+            node: ast::lower(ast),
+        }
+    }
+}
+
+/// Prints the equivalent pattern text, escaped where needed.
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.node {
+            Some(node) => node.fmt(f),
+            None => f.write_str("(?!)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod pattern_tests {
+    use crate::RegExp;
+
+    use super::*;
+
+    #[test]
+    fn build_and_match() {
+        let pattern = Pattern::lit("id-").then(Pattern::any_of("xyz").plus());
+        assert_eq!(pattern.to_string(), "id\\-(?:x|y|z)(?:x|y|z)*");
+
+        let regexp = RegExp::from_pattern(&pattern);
+        assert!(regexp.matches("id-zx".to_string()));
+        assert!(!regexp.matches("id-".to_string()));
+        assert!(!regexp.matches("id-xa".to_string()));
+
+        let digits = RegExp::from_pattern(&Pattern::any_of("0123456789").plus());
+        assert!(digits.matches("2026".to_string()));
+    }
+
+    #[test]
+    fn same_tree_as_the_parser() {
+        let pattern = Pattern::lit("a")
+            .or(Pattern::lit("bc"))
+            .or(Pattern::lit("def").star());
+        assert_eq!(
+            pattern.node.unwrap(),
+            Pattern::from(&RegExp::parse_ast("a|(?:bc|(?:def)*)"))
+                .node
+                .unwrap()
+        );
+        assert_eq!(Pattern::lit("*").optional().to_string(), "\\*|");
+    }
+
+    #[test]
+    fn never() {
+        let never = RegExp::from_pattern(&Pattern::any_of(""));
+        assert!(!never.matches("".to_string()));
+        assert_eq!(never.find("abc"), None);
+        assert_eq!(never.count_of_length(0).to_string(), "0");
+        assert_eq!(Pattern::alt([]).to_string(), "(?!)");
+
+        let pattern = Pattern::lit("a")
+            .or(Pattern::any_of(""))
+            .then(Pattern::never().star());
+        assert_eq!(pattern.to_string(), "a(?:)");
+        assert_eq!(Pattern::lit("a").then(Pattern::never()), Pattern::never());
+
+        // `(?!)` parses back to a union without alternatives
+        let ast = RegExp::parse_ast(&Pattern::never().to_string());
+        assert_eq!(ast.kind, crate::syntax::AstKind::Union(vec![]));
+        assert_eq!(Pattern::from(&ast), Pattern::never());
+        let regexp = RegExp::new("a(?!)|b(?!)*".to_string());
+        assert!(regexp.matches("b".to_string()) && !regexp.matches("a".to_string()));
+        assert_eq!(RegExp::parse_ast("a(?!)|b(?!)*").to_string(), "b(?:)");
+        let set = crate::RegExpSet::new(["(?!)", "a"]);
+        assert_eq!(set.matches("a"), vec![1]);
+    }
+}
@@ -0,0 +1,133 @@
+use super::{Ast, AstKind};
+
+/// Read-only depth-first traversal of an [`Ast`], in pattern order.
+pub trait Visitor {
+    /// called on a node before any of its children
+    fn visit_pre(&mut self, _ast: &Ast) {}
+
+    /// called on a node after all of its children
+    fn visit_post(&mut self, _ast: &Ast) {}
+}
+
+/// Rewrite of an [`Ast`]. Override [`Fold::fold`] for the nodes of interest and call
+/// [`fold_children`] to keep descending into the rest. The rewrite is top-down: an override sees
+/// each node before its children, unless it calls [`fold_children`] first.
+pub trait Fold {
+    fn fold(&mut self, ast: Ast) -> Ast {
+        fold_children(self, ast)
+    }
+}
+
+/// rebuild `ast` with every child passed through `folder`
+pub fn fold_children<F: Fold + ?Sized>(folder: &mut F, ast: Ast) -> Ast {
+    let kind = match ast.kind {
+        AstKind::Empty | AstKind::Literal(_) => ast.kind,
+        AstKind::Union(asts) => AstKind::Union(asts.into_iter().map(|a| folder.fold(a)).collect()),
+        AstKind::Concat(asts) => {
+            AstKind::Concat(asts.into_iter().map(|a| folder.fold(a)).collect())
+        }
+        AstKind::Star(ast) => AstKind::Star(Box::new(folder.fold(*ast))),
+        AstKind::Group(ast) => AstKind::Group(Box::new(folder.fold(*ast))),
+        AstKind::NonCapturing(ast) => AstKind::NonCapturing(Box::new(folder.fold(*ast))),
+        AstKind::NamedGroup(name, ast) => AstKind::NamedGroup(name, Box::new(folder.fold(*ast))),
+    };
+    Ast::new(kind, ast.span)
+}
+
+impl Ast {
+    /// walk the tree depth-first, calling `visitor` on every node
+    pub fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.visit_pre(self);
+        for child in self.children() {
+            child.visit(visitor);
+        }
+        visitor.visit_post(self);
+    }
+}
+
+#[cfg(test)]
+mod visit_tests {
+    use crate::syntax::Span;
+    use crate::RegExp;
+
+    use super::*;
+
+    struct Stars(Vec<Span>);
+
+    impl Visitor for Stars {
+        fn visit_pre(&mut self, ast: &Ast) {
+            if let AstKind::Star(_) = ast.kind {
+                self.0.push(ast.span);
+            }
+        }
+    }
+
+    /// `a` -> `b`
+    struct Replace;
+
+    impl Fold for Replace {
+        fn fold(&mut self, ast: Ast) -> Ast {
+            match ast.kind {
+                AstKind::Literal('a') => Ast::new(AstKind::Literal('b'), ast.span),
+                _ => fold_children(self, ast),
+            }
+        }
+    }
+
+    /// drops every `a` from unions and concatenations, after rewriting their children
+    struct DropA;
+
+    impl Fold for DropA {
+        fn fold(&mut self, ast: Ast) -> Ast {
+            let ast = fold_children(self, ast);
+            let keep = |asts: Vec<Ast>| {
+                let literal = AstKind::Literal('a');
+                asts.into_iter().filter(|ast| ast.kind != literal).collect()
+            };
+            let kind = match ast.kind {
+                AstKind::Union(asts) => AstKind::Union(keep(asts)),
+                AstKind::Concat(asts) => AstKind::Concat(keep(asts)),
+                kind => kind,
+            };
+            Ast::new(kind, ast.span)
+        }
+    }
+
+    #[test]
+    fn visit_with_spans() {
+        let mut stars = Stars(vec![]);
+        RegExp::parse_ast("a*(b|c*)").visit(&mut stars);
+        assert_eq!(stars.0, vec![Span::new(0, 2), Span::new(5, 7)]);
+    }
+
+    #[test]
+    fn fold_rewrites_literals() {
+        let ast = Replace.fold(RegExp::parse_ast("(a|c)*a"));
+        assert_eq!(ast.to_string(), "(b|c)*b");
+        assert_eq!(ast.span, Span::new(0, 7));
+    }
+
+    #[test]
+    fn fold_to_empty_operands() {
+        // `aa` leaves a concatenation of nothing, the empty string
+        let ast = DropA.fold(RegExp::parse_ast("aa"));
+        assert_eq!(ast.kind, AstKind::Concat(vec![]));
+        assert_eq!(ast.to_string(), "");
+        let regexp = RegExp::from_ast(&ast);
+        assert!(regexp.matches("".to_string()) && !regexp.matches("a".to_string()));
+
+        // `a|a` leaves a union of nothing, which matches nothing, and so does `b` after it
+        for pattern in ["a|a", "(?:a|a)b"] {
+            let ast = DropA.fold(RegExp::parse_ast(pattern));
+            assert_eq!(ast.to_string(), "(?!)");
+            let regexp = RegExp::from_ast(&ast);
+            assert!(!regexp.matches("".to_string()) && !regexp.matches("b".to_string()));
+            assert_eq!(regexp.find("ab"), None);
+        }
+
+        // an alternative that matches nothing drops out, and a star of it is the empty string
+        let ast = DropA.fold(RegExp::parse_ast("(?:a|a)|b(?:a|a)*"));
+        assert_eq!(ast.to_string(), "b(?:)");
+        assert!(RegExp::from_ast(&ast).matches("b".to_string()));
+    }
+}
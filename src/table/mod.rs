@@ -0,0 +1,133 @@
+//! DFAs compiled ahead of time into static tables, as by the `regex!` macro of the
+//! `regex-engine-macros` crate.
+
+use crate::compiler;
+use crate::syntax::Error;
+use crate::RegExp;
+
+/// A minimized DFA whose transitions are laid out in static arrays, so that matching needs no
+/// allocation and no compilation at run time.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticDFA {
+    start: usize,
+    accepts: &'static [bool],
+    /// the transitions out of each state, sorted by character
+    transitions: &'static [&'static [(char, usize)]],
+}
+
+impl StaticDFA {
+    #[doc(hidden)]
+    pub const fn from_parts(
+        start: usize,
+        accepts: &'static [bool],
+        transitions: &'static [&'static [(char, usize)]],
+    ) -> Self {
+        Self {
+            start,
+            accepts,
+            transitions,
+        }
+    }
+
+    /// Whether the whole of `input` matches, like [`RegExp::matches`].
+    pub fn matches(&self, input: &str) -> bool {
+        let mut state = self.start;
+        for ch in input.chars() {
+            let transitions = self.transitions[state];
+            match transitions.binary_search_by_key(&ch, |&(c, _)| c) {
+                Ok(i) => state = transitions[i].1,
+                Err(_) => return false,
+            }
+        }
+        self.accepts[state]
+    }
+
+    /// Number of DFA states.
+    pub fn len(&self) -> usize {
+        self.accepts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accepts.is_empty()
+    }
+}
+
+/// start state, accepting states and sorted transitions of the minimized DFA for `regex`
+type Tables = (usize, Vec<bool>, Vec<Vec<(char, usize)>>);
+
+fn tables(regex: &str) -> Result<Tables, Error> {
+    let node = compiler::ast::lower(&RegExp::try_parse_ast(regex)?);
+    let dfa = compiler::compile_lowered(node).nfa2dfa().minimize();
+    let accepts = dfa.states().map(|state| dfa.is_accept(state)).collect();
+    let transitions = dfa
+        .map
+        .iter()
+        .map(|map| map.iter().map(|(&ch, next)| (ch, next.id)).collect())
+        .collect();
+    Ok((dfa.start.id, accepts, transitions))
+}
+
+/// Rust source of a [`StaticDFA`] constant matching `regex`, referring to this crate as
+/// `::regex_engine`.
+#[doc(hidden)]
+pub fn source(regex: &str) -> Result<String, Error> {
+    let (start, accepts, transitions) = tables(regex)?;
+    let transitions: Vec<String> = transitions
+        .iter()
+        .map(|map| {
+            let edges: Vec<String> = map
+                .iter()
+                .map(|&(ch, next)| format!("('\\u{{{:x}}}', {})", u32::from(ch), next))
+                .collect();
+            format!("&[{}]", edges.join(", "))
+        })
+        .collect();
+    Ok(format!(
+        "::regex_engine::table::StaticDFA::from_parts({}, &{:?}, &[{}])",
+        start,
+        accepts,
+        transitions.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod table_tests {
+    use super::*;
+
+    fn leak(regex: &str) -> StaticDFA {
+        let (start, accepts, transitions) = tables(regex).unwrap();
+        let transitions: Vec<&'static [(char, usize)]> = transitions
+            .into_iter()
+            .map(|map| &*Vec::leak(map))
+            .collect();
+        StaticDFA::from_parts(start, Vec::leak(accepts), Vec::leak(transitions))
+    }
+
+    #[test]
+    fn agrees_with_regexp() {
+        for regex in ["(a|b)*abb", "a|", "\\é(?P<x>c*)", "(a|b)(a|b)"] {
+            let dfa = leak(regex);
+            let regexp = RegExp::new(regex.to_string());
+            for input in ["", "a", "abb", "babb", "é", "éccc", "ab", "abc"] {
+                assert_eq!(dfa.matches(input), regexp.matches(input.to_string()));
+            }
+        }
+        assert_eq!(leak("(a|b)*abb").len(), 4);
+    }
+
+    #[test]
+    fn source_and_errors() {
+        assert_eq!(
+            source("a").unwrap(),
+            "::regex_engine::table::StaticDFA::from_parts(\
+             0, &[false, true], &[&[('\\u{61}', 1)], &[]])"
+        );
+        let error = source("a(b").unwrap_err();
+        assert_eq!(error.message, "expected RParen, got Eof");
+        assert_eq!((error.span.start, error.span.end), (3, 3));
+        let error = source("ab|c?").unwrap_err();
+        assert_eq!((error.span.start, error.span.end), (4, 5));
+        let error = source("(?P<x>a)(?P<x>b)").unwrap_err();
+        assert_eq!(error.message, "duplicate group name: x");
+    }
+}
@@ -0,0 +1,226 @@
+//! Tokenizers generated from a list of rules.
+
+use std::ops::Range;
+
+use crate::automaton::{lazy::LazyDFA, nfa::NFA, StateSet};
+use crate::compiler::{self, lexer::Lexer, parser::Parser};
+
+/// A maximal-munch tokenizer: at each position it takes the longest prefix of the rest of the
+/// input that some rule matches, and when several rules match that prefix, the one listed first.
+/// All rules are compiled into one NFA, whose DFA is built lazily, only for the states that the
+/// input visits; each of those carries the highest-priority rule it accepts for.
+pub struct Tokenizer {
+    names: Vec<String>,
+    nfa: NFA,
+    /// accept states of each rule
+    rule_accepts: Vec<StateSet>,
+    /// the non-empty tokens of every rule, reversed, to find where tokens start
+    reverse: NFA,
+}
+
+/// A piece of the input matched by the rule named `kind`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'t> {
+    pub kind: &'t str,
+    /// byte range of the input
+    pub span: Range<usize>,
+}
+
+impl Tokenizer {
+    /// Compile `(name, pattern)` rules, in order of priority.
+    pub fn new<I, N, P>(rules: I) -> Self
+    where
+        I: IntoIterator<Item = (N, P)>,
+        N: Into<String>,
+        P: AsRef<str>,
+    {
+        let (names, nodes): (Vec<String>, Vec<_>) = rules
+            .into_iter()
+            .map(|(name, regex)| {
+                let tokens = Lexer::new(regex.as_ref().to_string()).tokenize();
+                (name.into(), Parser::new(tokens).parse_node())
+            })
+            .unzip();
+
+        let (nfa, rule_accepts) = compiler::compile_set(nodes);
+        let reverse = nfa.non_empty().reverse();
+        Self {
+            names,
+            nfa,
+            rule_accepts,
+            reverse,
+        }
+    }
+
+    /// Split `input` into tokens. Input that no rule matches is reported as `Err` with its byte
+    /// range, one for each run of such characters, and tokenizing resumes after it. Rules only
+    /// ever produce non-empty tokens.
+    pub fn tokenize<'t, 'h>(&'t self, input: &'h str) -> Tokens<'t, 'h> {
+        Tokens {
+            tokenizer: self,
+            input,
+            pos: 0,
+            dfa: LazyDFA::new(&self.nfa),
+            accepts: vec![],
+            starts: None,
+        }
+    }
+
+    /// whether a token starts at each byte offset of `input`, from a single right-to-left pass
+    /// of an unanchored lazy DFA for the reversed tokens
+    fn token_starts(&self, input: &str) -> Vec<bool> {
+        let mut dfa = LazyDFA::unanchored(&self.reverse);
+        let mut starts = vec![false; input.len()];
+        let mut state = 0;
+        for (offset, ch) in input.char_indices().rev() {
+            state = dfa.next(state, ch);
+            starts[offset] = dfa.is_accept(state);
+        }
+        starts
+    }
+}
+
+/// Iterator over the tokens of an input, see [`Tokenizer::tokenize`].
+pub struct Tokens<'t, 'h> {
+    tokenizer: &'t Tokenizer,
+    input: &'h str,
+    pos: usize,
+    /// the DFA of every rule, anchored where a token may start
+    dfa: LazyDFA<'t>,
+    /// highest-priority rule accepted in each state of `dfa` built so far
+    accepts: Vec<Option<usize>>,
+    /// [`Tokenizer::token_starts`] of the input, once some of it is unmatched
+    starts: Option<Vec<bool>>,
+}
+
+impl Tokens<'_, '_> {
+    /// the rule matching the longest non-empty prefix of `input[at..]`, and where that ends
+    fn longest(&mut self, at: usize) -> Option<(usize, usize)> {
+        let mut state = 0;
+        let mut longest = None;
+        for (offset, ch) in self.input[at..].char_indices() {
+            state = self.dfa.next(state, ch);
+            if self.dfa.is_dead(state) {
+                break;
+            }
+            if let Some(rule) = self.rule(state) {
+                longest = Some((rule, at + offset + ch.len_utf8()));
+            }
+        }
+        longest
+    }
+
+    /// highest-priority rule accepted in `state` of `dfa`
+    fn rule(&mut self, state: usize) -> Option<usize> {
+        while self.accepts.len() <= state {
+            let subset = self.dfa.subset(self.accepts.len());
+            let rule = self
+                .tokenizer
+                .rule_accepts
+                .iter()
+                .position(|accepts| !subset.is_disjoint(accepts));
+            self.accepts.push(rule);
+        }
+        self.accepts[state]
+    }
+}
+
+impl<'t> Iterator for Tokens<'t, '_> {
+    type Item = Result<Token<'t>, Range<usize>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        if start == self.input.len() {
+            return None;
+        }
+        if let Some((rule, end)) = self.longest(start) {
+            self.pos = end;
+            return Some(Ok(Token {
+                kind: &self.tokenizer.names[rule],
+                span: start..end,
+            }));
+        }
+        // skip to where the next token starts without trying every offset on the way
+        let starts = self
+            .starts
+            .get_or_insert_with(|| self.tokenizer.token_starts(self.input));
+        self.pos = starts[start + 1..]
+            .iter()
+            .position(|&starts| starts)
+            .map_or(self.input.len(), |offset| start + 1 + offset);
+        Some(Err(start..self.pos))
+    }
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use super::*;
+
+    fn kinds<'t>(tokenizer: &'t Tokenizer, input: &str) -> Vec<Result<&'t str, Range<usize>>> {
+        tokenizer
+            .tokenize(input)
+            .map(|token| token.map(|token| token.kind))
+            .collect()
+    }
+
+    #[test]
+    fn maximal_munch_and_priority() {
+        let tokenizer = Tokenizer::new([
+            ("if", "if"),
+            ("ident", "(a|b|f|i)(a|b|f|i)*"),
+            ("space", "\\ \\ *"),
+            ("eq", "\\="),
+            ("eqeq", "\\=\\="),
+        ]);
+        let tokens: Vec<_> = tokenizer
+            .tokenize("if iff==a")
+            .map(|token| token.map(|token| (token.kind, token.span)))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok(("if", 0..2)),
+                Ok(("space", 2..3)),
+                Ok(("ident", 3..6)),
+                Ok(("eqeq", 6..8)),
+                Ok(("ident", 8..9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unmatched_input() {
+        let tokenizer = Tokenizer::new([("a", "a"), ("b", "bb*"), ("empty", "c*")]);
+        assert_eq!(
+            kinds(&tokenizer, "xyabbz"),
+            vec![Err(0..2), Ok("a"), Ok("b"), Err(5..6)]
+        );
+        assert_eq!(kinds(&tokenizer, ""), vec![]);
+        // empty matches of `c*` are not tokens
+        assert_eq!(
+            kinds(&tokenizer, "ccxa"),
+            vec![Ok("empty"), Err(2..3), Ok("a")]
+        );
+    }
+
+    #[test]
+    fn exponential_dfa() {
+        // the full DFA would need a state for each of the last 20 characters seen
+        let pattern = "(a|b)*a".to_string() + &"(a|b)".repeat(20);
+        let tokenizer = Tokenizer::new([("tail", pattern.as_str()), ("c", "c")]);
+        let input = "a".to_string() + &"b".repeat(20) + "c";
+        assert_eq!(kinds(&tokenizer, &input), vec![Ok("tail"), Ok("c")]);
+    }
+
+    #[test]
+    fn long_unmatched_run() {
+        // every `a` starts a token that fails at the end, which each offset would rescan
+        let tokenizer = Tokenizer::new([("ab", "a*b"), ("c", "c")]);
+        let input = "a".repeat(100_000) + "c";
+        assert_eq!(kinds(&tokenizer, &input), vec![Err(0..100_000), Ok("c")]);
+        assert_eq!(
+            kinds(&tokenizer, "caacaab"),
+            vec![Ok("c"), Err(1..3), Ok("c"), Ok("ab")]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::{cmp::Ordering, fmt};
+
+use super::rng::Rng;
+
+/// arbitrary precision unsigned integer, stored as little-endian 32-bit limbs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        Self { limbs: vec![] }
+    }
+
+    pub fn one() -> Self {
+        Self::from(1u64)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() * 32 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0u64;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = self.limb(i) as u64 + other.limb(i) as u64 + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+        Self::from_limbs(limbs)
+    }
+
+    /// `self - other`, panics if `other` is larger
+    pub fn sub(&self, other: &Self) -> Self {
+        assert!(*self >= *other, "BigUint subtraction underflow");
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0i64;
+        for i in 0..self.limbs.len() {
+            let mut diff = self.limb(i) as i64 - other.limb(i) as i64 - borrow;
+            borrow = 0;
+            if diff < 0 {
+                diff += 1 << 32;
+                borrow = 1;
+            }
+            limbs.push(diff as u32);
+        }
+        Self::from_limbs(limbs)
+    }
+
+    /// uniformly random value in `0..self`, panics if `self` is zero
+    pub fn random_below(&self, rng: &mut Rng) -> Self {
+        assert!(!self.is_zero(), "BigUint::random_below on zero");
+        let bits = self.bits();
+        loop {
+            let mut limbs: Vec<u32> = (0..self.limbs.len())
+                .map(|_| rng.next_u64() as u32)
+                .collect();
+            let excess = self.limbs.len() * 32 - bits;
+            if let Some(top) = limbs.last_mut() {
+                *top = top.checked_shr(excess as u32).unwrap_or(0);
+            }
+            let candidate = Self::from_limbs(limbs);
+            if candidate < *self {
+                return candidate;
+            }
+        }
+    }
+
+    /// `(self / divisor, self % divisor)`
+    pub fn div_rem_small(&self, divisor: u32) -> (Self, u32) {
+        let mut limbs = vec![0; self.limbs.len()];
+        let mut rem = 0u64;
+        for i in (0..self.limbs.len()).rev() {
+            let cur = (rem << 32) | self.limbs[i] as u64;
+            limbs[i] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        (Self::from_limbs(limbs), rem as u32)
+    }
+
+    fn limb(&self, i: usize) -> u32 {
+        self.limbs.get(i).copied().unwrap_or(0)
+    }
+
+    fn from_limbs(mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self { limbs }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        Self::from_limbs(vec![value as u32, (value >> 32) as u32])
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return f.pad("0");
+        }
+        // peel off nine decimal digits at a time
+        let mut chunks = vec![];
+        let mut rest = self.clone();
+        while !rest.is_zero() {
+            let (quotient, chunk) = rest.div_rem_small(1_000_000_000);
+            chunks.push(chunk);
+            rest = quotient;
+        }
+        let mut digits = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            digits.push_str(&format!("{:09}", chunk));
+        }
+        f.pad(&digits)
+    }
+}
@@ -0,0 +1,2 @@
+pub mod biguint;
+pub mod rng;
@@ -12,7 +12,7 @@ impl GraphViz {
     }
 
     fn add_epilogue(&self, output: &mut String) {
-        output.push_str("}");
+        output.push('}');
     }
 
     fn add_node(&self, output: &mut String, node_id: usize, is_accept: bool) {
@@ -29,7 +29,7 @@ impl GraphViz {
             "  {} -> {} [label = \"{}\"];\n",
             from,
             to,
-            if label.len() == 0 { "ε" } else { label }
+            if label.is_empty() { "ε" } else { label }
         ));
     }
 
@@ -49,9 +49,8 @@ impl GraphViz {
             for state in v {
                 self.add_edge(&mut edge_output, k.current_state.id, state.id, &k.input);
                 node_set.insert(k.current_state);
-                node_set.insert(state.clone());
+                node_set.insert(*state);
             }
-            k.current_state;
         }
 
         for state in &node_set {
@@ -69,7 +68,7 @@ impl GraphViz {
         use std::fs::File;
         use std::io::{BufWriter, Write};
 
-        let f = File::create(&filename).unwrap();
+        let f = File::create(filename).unwrap();
         let mut writer = BufWriter::new(f);
         writer.write_all(output.as_bytes()).unwrap();
     }